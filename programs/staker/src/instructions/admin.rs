@@ -0,0 +1,89 @@
+use anchor_lang::prelude::*;
+
+use crate::{AdminChanged, AdminProposed, ErrorCode, RolesChanged, StakingAccount};
+
+#[derive(Accounts)]
+pub struct ProposeAdmin<'info> {
+    pub initializer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [ crate::constants::STAKING_PDA_SEED, staking.stake_token_mint.as_ref() ],
+        bump,
+        constraint = staking.initializer_key == *initializer.key @ ErrorCode::Unauthorized,
+    )]
+    pub staking: Account<'info, StakingAccount>,
+}
+
+pub fn propose_admin(ctx: Context<ProposeAdmin>, new_admin: Pubkey) -> Result<()> {
+    //the default key cancels a pending proposal
+    ctx.accounts.staking.pending_admin = new_admin;
+
+    emit!(AdminProposed {
+        admin: ctx.accounts.staking.initializer_key,
+        pending_admin: new_admin,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    pub pending_admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [ crate::constants::STAKING_PDA_SEED, staking.stake_token_mint.as_ref() ],
+        bump,
+        constraint = staking.pending_admin == *pending_admin.key @ ErrorCode::Unauthorized,
+    )]
+    pub staking: Account<'info, StakingAccount>,
+}
+
+pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+    let staking = &mut ctx.accounts.staking;
+    let old_admin = staking.initializer_key;
+
+    staking.initializer_key = staking.pending_admin;
+    staking.pending_admin = Pubkey::default();
+
+    emit!(AdminChanged {
+        old_admin,
+        new_admin: staking.initializer_key,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetRoles<'info> {
+    pub initializer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [ crate::constants::STAKING_PDA_SEED, staking.stake_token_mint.as_ref() ],
+        bump,
+        constraint = staking.initializer_key == *initializer.key @ ErrorCode::Unauthorized,
+    )]
+    pub staking: Account<'info, StakingAccount>,
+}
+
+pub fn set_roles(
+    ctx: Context<SetRoles>,
+    pauser: Pubkey,
+    fee_manager: Pubkey,
+    parameter_manager: Pubkey,
+) -> Result<()> {
+    let staking = &mut ctx.accounts.staking;
+    staking.pauser = pauser;
+    staking.fee_manager = fee_manager;
+    staking.parameter_manager = parameter_manager;
+
+    emit!(RolesChanged {
+        pauser,
+        fee_manager,
+        parameter_manager,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,214 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface;
+
+use crate::instructions::{accrue_rewards, close_escrow, compound_emission, transfer_from_escrow};
+use crate::math::SafeMath;
+use crate::{ErrorCode, PositionClosed, StakingAccount, UserStakingAccount, DUST_THRESHOLD};
+
+#[derive(Accounts)]
+pub struct ClosePosition<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// the vault is the authority of every position escrow
+    #[account(
+        seeds = [ staking.stake_token_mint.as_ref() ],
+        bump,
+    )]
+    pub stake_token_vault: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [ crate::constants::STAKING_PDA_SEED, staking.stake_token_mint.as_ref() ],
+        bump,
+    )]
+    pub staking: Account<'info, StakingAccount>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [ staking.key().as_ref(), user.key().as_ref() ],
+        bump,
+    )]
+    pub user_staking: Account<'info, UserStakingAccount>,
+
+    #[account(
+        mut,
+        seeds = [ crate::constants::POSITION_ESCROW_SEED, user_staking.key().as_ref() ],
+        bump,
+    )]
+    pub position_escrow: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    pub x_token_program: Interface<'info, token_interface::TokenInterface>,
+}
+
+pub fn close_position(ctx: Context<ClosePosition>) -> Result<()> {
+    let staking = &mut ctx.accounts.staking;
+    let user_staking = &ctx.accounts.user_staking;
+
+    require!(
+        ctx.accounts.position_escrow.amount == 0
+            && user_staking.reward_shares == 0
+            && user_staking.rewards_owed.iter().all(|owed| *owed == 0)
+            && user_staking.open_unstake_requests == 0,
+        ErrorCode::PositionNotEmpty
+    );
+
+    //emission worth less than a share was left uncompounded, it stays in the vault
+    staking.emission_owed = staking.emission_owed.safe_sub(user_staking.emission_owed)?;
+
+    close_escrow(
+        &ctx.accounts.position_escrow,
+        ctx.accounts.user.to_account_info(),
+        &ctx.accounts.stake_token_vault,
+        &ctx.accounts.x_token_program,
+        ctx.bumps.stake_token_vault,
+    )?;
+
+    emit!(PositionClosed {
+        user: ctx.accounts.user.key(),
+        swept: false,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SweepPosition<'info> {
+    pub initializer: Signer<'info>,
+
+    #[account(address = staking.stake_token_mint)]
+    pub stake_token_mint: Box<InterfaceAccount<'info, token_interface::Mint>>,
+
+    /// the vault is the authority of every position escrow
+    #[account(
+        mut,
+        seeds = [ staking.stake_token_mint.as_ref() ],
+        bump,
+    )]
+    pub stake_token_vault: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [ crate::constants::STAKING_PDA_SEED, staking.stake_token_mint.as_ref() ],
+        bump,
+        constraint = staking.initializer_key == *initializer.key @ ErrorCode::Unauthorized,
+    )]
+    pub staking: Account<'info, StakingAccount>,
+
+    /// the reserve rewards are dripped from
+    #[account(
+        mut,
+        seeds = [ crate::constants::REWARD_RESERVE_SEED, staking.stake_token_mint.as_ref() ],
+        bump,
+    )]
+    pub reward_reserve: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    /// CHECK: the position owner, receives the rent
+    #[account(mut)]
+    pub user: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [ staking.key().as_ref(), user.key().as_ref() ],
+        bump,
+    )]
+    pub user_staking: Account<'info, UserStakingAccount>,
+
+    #[account(
+        mut,
+        address = staking.x_token_mint,
+    )]
+    pub x_token_mint: Box<InterfaceAccount<'info, token_interface::Mint>>,
+
+    #[account(
+        mut,
+        seeds = [ crate::constants::POSITION_ESCROW_SEED, user_staking.key().as_ref() ],
+        bump,
+    )]
+    pub position_escrow: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    /// the owner's x token account, receives the dust shares
+    #[account(
+        mut,
+        token::mint = x_token_mint,
+        token::authority = user,
+        token::token_program = x_token_program,
+    )]
+    pub user_x_token_account: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    pub token_program: Interface<'info, token_interface::TokenInterface>,
+    pub x_token_program: Interface<'info, token_interface::TokenInterface>,
+}
+
+pub fn sweep_position(ctx: Context<SweepPosition>) -> Result<()> {
+    //settle and compound up to now so accrued rewards count against the threshold
+    accrue_rewards(
+        &mut ctx.accounts.staking,
+        &ctx.accounts.stake_token_mint,
+        &mut ctx.accounts.reward_reserve,
+        &mut ctx.accounts.stake_token_vault,
+        &ctx.accounts.token_program,
+        ctx.bumps.stake_token_vault,
+    )?;
+    compound_emission(
+        &mut ctx.accounts.staking,
+        &mut ctx.accounts.user_staking,
+        &mut ctx.accounts.x_token_mint,
+        &mut ctx.accounts.position_escrow,
+        &ctx.accounts.stake_token_vault,
+        &ctx.accounts.x_token_program,
+        ctx.bumps.stake_token_vault,
+    )?;
+
+    let staking = &mut ctx.accounts.staking;
+    let user_staking = &mut ctx.accounts.user_staking;
+    let escrowed = ctx.accounts.position_escrow.amount;
+
+    require!(
+        escrowed <= DUST_THRESHOLD
+            && user_staking
+                .rewards_owed
+                .iter()
+                .all(|owed| *owed <= DUST_THRESHOLD)
+            && user_staking.open_unstake_requests == 0,
+        ErrorCode::PositionNotEmpty
+    );
+
+    staking.total_reward_shares = staking
+        .total_reward_shares
+        .safe_sub(user_staking.reward_shares)?;
+    user_staking.reward_shares = 0;
+
+    //emission worth less than a share was left uncompounded, it stays in the vault
+    staking.emission_owed = staking.emission_owed.safe_sub(user_staking.emission_owed)?;
+    user_staking.emission_owed = 0;
+
+    //hand the dust shares back to the owner before closing the escrow
+    if escrowed > 0 {
+        transfer_from_escrow(
+            &ctx.accounts.position_escrow,
+            ctx.accounts.user_x_token_account.to_account_info(),
+            &ctx.accounts.x_token_mint,
+            &ctx.accounts.stake_token_vault,
+            &ctx.accounts.x_token_program,
+            ctx.bumps.stake_token_vault,
+            escrowed,
+        )?;
+    }
+    close_escrow(
+        &ctx.accounts.position_escrow,
+        ctx.accounts.user.to_account_info(),
+        &ctx.accounts.stake_token_vault,
+        &ctx.accounts.x_token_program,
+        ctx.bumps.stake_token_vault,
+    )?;
+
+    emit!(PositionClosed {
+        user: ctx.accounts.user.key(),
+        swept: true,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,167 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface;
+
+use crate::instructions::get_price;
+use crate::math::{mul_div, Rounding, SafeMath};
+use crate::{RewardEmission, StakingAccount, UserStakingAccount, REWARD_PRECISION};
+
+#[derive(Accounts)]
+pub struct Crank<'info> {
+    pub stake_token_mint: Box<InterfaceAccount<'info, token_interface::Mint>>,
+
+    #[account(
+        mut,
+        seeds = [ stake_token_mint.key().as_ref() ],
+        bump,
+    )]
+    pub stake_token_vault: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [ crate::constants::STAKING_PDA_SEED, stake_token_mint.key().as_ref() ],
+        bump,
+    )]
+    pub staking: Account<'info, StakingAccount>,
+
+    /// the reserve rewards are dripped from
+    #[account(
+        mut,
+        seeds = [ crate::constants::REWARD_RESERVE_SEED, stake_token_mint.key().as_ref() ],
+        bump,
+    )]
+    pub reward_reserve: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    pub token_program: Interface<'info, token_interface::TokenInterface>,
+}
+
+pub fn crank(ctx: Context<Crank>) -> Result<()> {
+    accrue_rewards(
+        &mut ctx.accounts.staking,
+        &ctx.accounts.stake_token_mint,
+        &mut ctx.accounts.reward_reserve,
+        &mut ctx.accounts.stake_token_vault,
+        &ctx.accounts.token_program,
+        ctx.bumps.stake_token_vault,
+    )
+}
+
+/// moves the rewards emitted since the last accrual from the reserve into the
+/// vault and shares them by reward weight. must run before any weight changes
+pub fn accrue_rewards<'info>(
+    staking: &mut Account<'info, StakingAccount>,
+    stake_token_mint: &InterfaceAccount<'info, token_interface::Mint>,
+    reward_reserve: &mut InterfaceAccount<'info, token_interface::TokenAccount>,
+    stake_token_vault: &mut InterfaceAccount<'info, token_interface::TokenAccount>,
+    token_program: &Interface<'info, token_interface::TokenInterface>,
+    vault_bump: u8,
+) -> Result<()> {
+    let now_ts = Clock::get()?.unix_timestamp;
+    staking.accrue_emission(now_ts as u64);
+
+    //nothing is emitted while no position carries reward weight
+    if staking.total_reward_shares == 0 {
+        staking.pending_emission = 0;
+        return Ok(());
+    }
+
+    //an empty reserve forfeits what could not be paid
+    let amount = staking.pending_emission.min(reward_reserve.amount);
+    staking.pending_emission = 0;
+
+    if amount == 0 {
+        return Ok(());
+    }
+
+    //compute vault signer seeds
+    let stake_token_mint_key = staking.stake_token_mint;
+    let seeds = &[stake_token_mint_key.as_ref(), &[vault_bump]];
+    let signer = &[&seeds[..]];
+
+    //transfer from reserve to vault
+    let vault_before = stake_token_vault.amount;
+    let cpi_ctx = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        token_interface::TransferChecked {
+            from: reward_reserve.to_account_info(),
+            mint: stake_token_mint.to_account_info(),
+            to: stake_token_vault.to_account_info(),
+            authority: stake_token_vault.to_account_info(),
+        },
+        signer,
+    );
+    token_interface::transfer_checked(cpi_ctx, amount, stake_token_mint.decimals)?;
+
+    reward_reserve.reload()?;
+    stake_token_vault.reload()?;
+
+    //emitted rewards are owed to the positions until they compound them,
+    //a transfer fee mint delivers less than was sent
+    let received = stake_token_vault.amount.safe_sub(vault_before)?;
+    staking.emission_per_share = (received as u128)
+        .safe_mul(REWARD_PRECISION)?
+        .safe_div(staking.total_reward_shares as u128)?
+        .safe_add(staking.emission_per_share)?;
+    staking.emission_owed = staking.emission_owed.safe_add(received)?;
+    staking.check_backing(stake_token_vault.amount)?;
+
+    emit!(RewardEmission { amount: received });
+
+    Ok(())
+}
+
+/// settles the position and mints its owed emission into the escrow at the
+/// current rate, so longer locks compound into more shares. the tokens are
+/// already in the vault. must follow `accrue_rewards`, returns the minted shares
+pub fn compound_emission<'info>(
+    staking: &mut StakingAccount,
+    user_staking: &mut UserStakingAccount,
+    x_token_mint: &mut InterfaceAccount<'info, token_interface::Mint>,
+    position_escrow: &mut InterfaceAccount<'info, token_interface::TokenAccount>,
+    stake_token_vault: &InterfaceAccount<'info, token_interface::TokenAccount>,
+    x_token_program: &Interface<'info, token_interface::TokenInterface>,
+    vault_bump: u8,
+) -> Result<u64> {
+    let now_ts = Clock::get()?.unix_timestamp as u64;
+    staking.update_rewards(now_ts)?;
+    user_staking.settle_rewards(staking)?;
+    user_staking.reset_reward_debts(staking)?;
+
+    //an amount worth less than a share waits for more emission
+    let owed = user_staking.emission_owed;
+    let shares = get_price(staking, x_token_mint).to_shares(owed, Rounding::Down)?;
+    if shares == 0 {
+        return Ok(0);
+    }
+
+    user_staking.emission_owed = 0;
+    staking.emission_owed = staking.emission_owed.safe_sub(owed)?;
+    staking.total_staked = staking.total_staked.safe_add(owed)?;
+
+    //compute vault signer seeds
+    let stake_token_mint_key = stake_token_vault.mint;
+    let seeds = &[stake_token_mint_key.as_ref(), &[vault_bump]];
+    let signer = &[&seeds[..]];
+
+    let cpi_ctx = CpiContext::new_with_signer(
+        x_token_program.to_account_info(),
+        token_interface::MintTo {
+            mint: x_token_mint.to_account_info(),
+            to: position_escrow.to_account_info(),
+            authority: stake_token_vault.to_account_info(),
+        },
+        signer,
+    );
+    token_interface::mint_to(cpi_ctx, shares)?;
+
+    //the compounded shares carry the weight per share the position already has
+    let escrowed = position_escrow.amount;
+    if escrowed > 0 {
+        let weight = mul_div(shares, user_staking.reward_shares, escrowed)?;
+        user_staking.add_reward_shares(staking, weight, now_ts)?;
+    }
+
+    x_token_mint.reload()?;
+    position_escrow.reload()?;
+
+    Ok(shares)
+}
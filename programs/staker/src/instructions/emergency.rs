@@ -0,0 +1,272 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface;
+
+use crate::instructions::{accrue_rewards, close_escrow};
+use crate::math::SafeMath;
+use crate::{EmergencyUnstaked, EmergencyWithdrawn, ErrorCode, StakingAccount, UserStakingAccount};
+
+#[derive(Accounts)]
+pub struct EmergencyWithdraw<'info> {
+    pub stake_token_mint: Box<InterfaceAccount<'info, token_interface::Mint>>,
+
+    //receives the position rent
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [ stake_token_mint.key().as_ref() ],
+        bump,
+    )]
+    pub stake_token_vault: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [ crate::constants::STAKING_PDA_SEED, stake_token_mint.key().as_ref() ],
+        bump,
+        constraint = staking.emergency_mode @ ErrorCode::NotEmergencyMode,
+    )]
+    pub staking: Account<'info, StakingAccount>,
+
+    /// the reserve rewards are dripped from
+    #[account(
+        mut,
+        seeds = [ crate::constants::REWARD_RESERVE_SEED, stake_token_mint.key().as_ref() ],
+        bump,
+    )]
+    pub reward_reserve: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    /// pending unstake requests are withdrawn first, they pay out right
+    /// away in emergency mode
+    #[account(
+        mut,
+        close = user,
+        seeds = [ staking.key().as_ref(), user.key().as_ref() ],
+        bump,
+        constraint = user_staking.open_unstake_requests == 0 @ ErrorCode::PositionNotEmpty,
+    )]
+    pub user_staking: Account<'info, UserStakingAccount>,
+
+    #[account(
+        mut,
+        address = staking.x_token_mint,
+    )]
+    pub x_token_mint: Box<InterfaceAccount<'info, token_interface::Mint>>,
+
+    /// the position's shares, all of them are given up
+    #[account(
+        mut,
+        seeds = [ crate::constants::POSITION_ESCROW_SEED, user_staking.key().as_ref() ],
+        bump,
+    )]
+    pub position_escrow: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    #[account(
+        mut,
+        token::mint = stake_token_mint,
+        token::authority = user,
+    )]
+    //the token account to send token
+    pub token_to: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    pub token_program: Interface<'info, token_interface::TokenInterface>,
+    pub x_token_program: Interface<'info, token_interface::TokenInterface>,
+}
+
+pub fn emergency_withdraw(ctx: Context<EmergencyWithdraw>) -> Result<()> {
+    accrue_rewards(
+        &mut ctx.accounts.staking,
+        &ctx.accounts.stake_token_mint,
+        &mut ctx.accounts.reward_reserve,
+        &mut ctx.accounts.stake_token_vault,
+        &ctx.accounts.token_program,
+        ctx.bumps.stake_token_vault,
+    )?;
+
+    //the position's shares pay at most their principal, valued against what
+    //the vault still holds so a shortfall is shared pro rata
+    let principal = ctx.accounts.user_staking.amount;
+    let x_token_amount = ctx.accounts.position_escrow.amount;
+    let what = ctx.accounts.staking.emergency_payout(
+        ctx.accounts.stake_token_vault.amount,
+        ctx.accounts.x_token_mint.supply,
+        x_token_amount,
+        principal,
+    )?;
+
+    //compute vault signer seeds
+    let stake_token_mint_key = ctx.accounts.stake_token_mint.key();
+    let seeds = &[
+        stake_token_mint_key.as_ref(),
+        &[ctx.bumps.stake_token_vault],
+    ];
+    let signer = &[&seeds[..]];
+
+    //burn the shares, the position no longer backs them
+    if x_token_amount > 0 {
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.x_token_program.to_account_info(),
+            token_interface::Burn {
+                mint: ctx.accounts.x_token_mint.to_account_info(),
+                from: ctx.accounts.position_escrow.to_account_info(),
+                authority: ctx.accounts.stake_token_vault.to_account_info(),
+            },
+            signer,
+        );
+        token_interface::burn(cpi_ctx, x_token_amount)?;
+    }
+    close_escrow(
+        &ctx.accounts.position_escrow,
+        ctx.accounts.user.to_account_info(),
+        &ctx.accounts.stake_token_vault,
+        &ctx.accounts.x_token_program,
+        ctx.bumps.stake_token_vault,
+    )?;
+
+    //forfeit unclaimed rewards, the remaining positions keep the rewards
+    //accrued up to now at the old total. forfeited emission stays in the vault
+    let now_ts = Clock::get()?.unix_timestamp as u64;
+    let staking = &mut ctx.accounts.staking;
+    let user_staking = &mut ctx.accounts.user_staking;
+    staking.update_rewards(now_ts)?;
+    user_staking.settle_rewards(staking)?;
+    staking.emission_owed = staking.emission_owed.safe_sub(user_staking.emission_owed)?;
+    staking.total_reward_shares = staking
+        .total_reward_shares
+        .safe_sub(user_staking.reward_shares)?;
+    user_staking.reward_shares = 0;
+    staking.total_staked = staking.total_staked.safe_sub(what)?;
+
+    if what > 0 {
+        //transfer from vault to user
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            token_interface::TransferChecked {
+                from: ctx.accounts.stake_token_vault.to_account_info(),
+                mint: ctx.accounts.stake_token_mint.to_account_info(),
+                to: ctx.accounts.token_to.to_account_info(),
+                authority: ctx.accounts.stake_token_vault.to_account_info(),
+            },
+            signer,
+        );
+        token_interface::transfer_checked(cpi_ctx, what, ctx.accounts.stake_token_mint.decimals)?;
+    }
+
+    emit!(EmergencyWithdrawn {
+        user: ctx.accounts.user.key(),
+        x_token_amount,
+        principal,
+        amount: what,
+    });
+
+    Ok(())
+}
+
+/// the emergency exit for x tokens held outside of a position escrow
+#[derive(Accounts)]
+#[instruction(amount: u64)]
+pub struct EmergencyUnstake<'info> {
+    pub stake_token_mint: Box<InterfaceAccount<'info, token_interface::Mint>>,
+
+    //the authority allowed to burn from user_x_token_account
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [ stake_token_mint.key().as_ref() ],
+        bump,
+    )]
+    pub stake_token_vault: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [ crate::constants::STAKING_PDA_SEED, stake_token_mint.key().as_ref() ],
+        bump,
+        constraint = staking.emergency_mode @ ErrorCode::NotEmergencyMode,
+    )]
+    pub staking: Account<'info, StakingAccount>,
+
+    #[account(
+        mut,
+        address = staking.x_token_mint,
+    )]
+    pub x_token_mint: Box<InterfaceAccount<'info, token_interface::Mint>>,
+
+    /// the x token account to burn from
+    #[account(
+        mut,
+        token::mint = x_token_mint,
+        token::authority = user,
+        token::token_program = x_token_program,
+        constraint = user_x_token_account.amount >= amount @ ErrorCode::InsufficientShares
+    )]
+    pub user_x_token_account: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    #[account(
+        mut,
+        token::mint = stake_token_mint,
+        token::authority = user,
+    )]
+    //the token account to send token
+    pub token_to: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    pub token_program: Interface<'info, token_interface::TokenInterface>,
+    pub x_token_program: Interface<'info, token_interface::TokenInterface>,
+}
+
+pub fn emergency_unstake(ctx: Context<EmergencyUnstake>, amount: u64) -> Result<()> {
+    require!(amount > 0, ErrorCode::ZeroAmount);
+
+    //shares outside of a position have no principal, they pay their value
+    //against what the vault still holds
+    let what = ctx.accounts.staking.emergency_value(
+        ctx.accounts.stake_token_vault.amount,
+        ctx.accounts.x_token_mint.supply,
+        amount,
+    )?;
+
+    //burn what is being sent
+    let cpi_ctx = CpiContext::new(
+        ctx.accounts.x_token_program.to_account_info(),
+        token_interface::Burn {
+            mint: ctx.accounts.x_token_mint.to_account_info(),
+            from: ctx.accounts.user_x_token_account.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        },
+    );
+    token_interface::burn(cpi_ctx, amount)?;
+
+    let staking = &mut ctx.accounts.staking;
+    staking.total_staked = staking.total_staked.safe_sub(what)?;
+
+    if what > 0 {
+        //compute vault signer seeds
+        let stake_token_mint_key = ctx.accounts.stake_token_mint.key();
+        let seeds = &[
+            stake_token_mint_key.as_ref(),
+            &[ctx.bumps.stake_token_vault],
+        ];
+        let signer = &[&seeds[..]];
+
+        //transfer from vault to user
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            token_interface::TransferChecked {
+                from: ctx.accounts.stake_token_vault.to_account_info(),
+                mint: ctx.accounts.stake_token_mint.to_account_info(),
+                to: ctx.accounts.token_to.to_account_info(),
+                authority: ctx.accounts.stake_token_vault.to_account_info(),
+            },
+            signer,
+        );
+        token_interface::transfer_checked(cpi_ctx, what, ctx.accounts.stake_token_mint.decimals)?;
+    }
+
+    emit!(EmergencyUnstaked {
+        user: ctx.accounts.user.key(),
+        x_token_amount: amount,
+        amount: what,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,188 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface;
+
+use crate::instructions::{accrue_rewards, compound_emission, get_price};
+use crate::math::{Rounding, SafeMath};
+use crate::{
+    AdminForcedExit, ErrorCode, PriceChange, StakingAccount, UserStakingAccount,
+    PAUSE_ADMIN_WITHDRAW,
+};
+
+#[derive(Accounts)]
+#[instruction(amount: u64)]
+pub struct ForceExit<'info> {
+    pub stake_token_mint: Box<InterfaceAccount<'info, token_interface::Mint>>,
+
+    #[account(constraint = staking.initializer_key == *admin.key @ ErrorCode::Unauthorized)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [ stake_token_mint.key().as_ref() ],
+        bump,
+    )]
+    pub stake_token_vault: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [ crate::constants::STAKING_PDA_SEED, stake_token_mint.key().as_ref() ],
+        bump,
+        constraint = !staking.is_paused(PAUSE_ADMIN_WITHDRAW) @ ErrorCode::Paused,
+    )]
+    pub staking: Account<'info, StakingAccount>,
+
+    /// the reserve rewards are dripped from
+    #[account(
+        mut,
+        seeds = [ crate::constants::REWARD_RESERVE_SEED, stake_token_mint.key().as_ref() ],
+        bump,
+    )]
+    pub reward_reserve: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    /// CHECK: the position owner, only used for addresses and ownership checks
+    pub user: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [ staking.key().as_ref(), user.key().as_ref() ],
+        bump,
+    )]
+    pub user_staking: Account<'info, UserStakingAccount>,
+
+    #[account(
+        mut,
+        address = staking.x_token_mint,
+    )]
+    pub x_token_mint: Box<InterfaceAccount<'info, token_interface::Mint>>,
+
+    /// only shares escrowed by the position can be forced out
+    #[account(
+        mut,
+        seeds = [ crate::constants::POSITION_ESCROW_SEED, user_staking.key().as_ref() ],
+        bump,
+        constraint = position_escrow.amount >= amount @ ErrorCode::InsufficientShares
+    )]
+    pub position_escrow: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    /// the position owner's token account, the only possible destination
+    #[account(
+        mut,
+        token::mint = stake_token_mint,
+        token::authority = user,
+    )]
+    pub token_to: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    pub token_program: Interface<'info, token_interface::TokenInterface>,
+    pub x_token_program: Interface<'info, token_interface::TokenInterface>,
+}
+
+pub fn force_exit(ctx: Context<ForceExit>, amount: u64, reason: u8) -> Result<()> {
+    require!(amount > 0, ErrorCode::ZeroAmount);
+    require!(reason != 0, ErrorCode::MissingReason);
+
+    //locks are only bypassed in emergency mode
+    let now_ts = Clock::get()?.unix_timestamp as u64;
+    let lock_end_date = ctx.accounts.user_staking.lock_end_date;
+    if !ctx.accounts.staking.emergency_mode && now_ts < lock_end_date {
+        msg!("Locked for {} more seconds", lock_end_date - now_ts);
+        return err!(ErrorCode::NotExceedLockEndDate);
+    }
+
+    accrue_rewards(
+        &mut ctx.accounts.staking,
+        &ctx.accounts.stake_token_mint,
+        &mut ctx.accounts.reward_reserve,
+        &mut ctx.accounts.stake_token_vault,
+        &ctx.accounts.token_program,
+        ctx.bumps.stake_token_vault,
+    )?;
+    compound_emission(
+        &mut ctx.accounts.staking,
+        &mut ctx.accounts.user_staking,
+        &mut ctx.accounts.x_token_mint,
+        &mut ctx.accounts.position_escrow,
+        &ctx.accounts.stake_token_vault,
+        &ctx.accounts.x_token_program,
+        ctx.bumps.stake_token_vault,
+    )?;
+
+    let old_price = get_price(&ctx.accounts.staking, &ctx.accounts.x_token_mint);
+
+    //compute vault signer seeds
+    let stake_token_mint_key = ctx.accounts.stake_token_mint.key();
+    let seeds = &[
+        stake_token_mint_key.as_ref(),
+        &[ctx.bumps.stake_token_vault],
+    ];
+    let signer = &[&seeds[..]];
+
+    let escrowed = ctx.accounts.position_escrow.amount;
+
+    //burn what is being sent, the vault is the authority of the escrow
+    let cpi_ctx = CpiContext::new_with_signer(
+        ctx.accounts.x_token_program.to_account_info(),
+        token_interface::Burn {
+            mint: ctx.accounts.x_token_mint.to_account_info(),
+            from: ctx.accounts.position_escrow.to_account_info(),
+            authority: ctx.accounts.stake_token_vault.to_account_info(),
+        },
+        signer,
+    );
+    token_interface::burn(cpi_ctx, amount)?;
+
+    let principal = ctx.accounts.user_staking.remove_shares(
+        &mut ctx.accounts.staking,
+        amount,
+        escrowed,
+        now_ts,
+    )?;
+
+    //determine user share of vault, emergency mode pays what emergency_withdraw would
+    let what = if ctx.accounts.staking.emergency_mode {
+        ctx.accounts.staking.emergency_payout(
+            ctx.accounts.stake_token_vault.amount,
+            ctx.accounts.x_token_mint.supply,
+            amount,
+            principal,
+        )?
+    } else {
+        old_price.to_tokens(amount, Rounding::Down)?
+    };
+
+    //transfer from vault to user
+    let cpi_ctx = CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        token_interface::TransferChecked {
+            from: ctx.accounts.stake_token_vault.to_account_info(),
+            mint: ctx.accounts.stake_token_mint.to_account_info(),
+            to: ctx.accounts.token_to.to_account_info(),
+            authority: ctx.accounts.stake_token_vault.to_account_info(),
+        },
+        signer,
+    );
+    token_interface::transfer_checked(cpi_ctx, what, ctx.accounts.stake_token_mint.decimals)?;
+    ctx.accounts.staking.total_staked = ctx.accounts.staking.total_staked.safe_sub(what)?;
+
+    ctx.accounts.stake_token_vault.reload()?;
+    ctx.accounts.x_token_mint.reload()?;
+    ctx.accounts
+        .staking
+        .check_backing(ctx.accounts.stake_token_vault.amount)?;
+
+    let new_price = get_price(&ctx.accounts.staking, &ctx.accounts.x_token_mint);
+
+    emit!(AdminForcedExit {
+        admin: ctx.accounts.admin.key(),
+        user: ctx.accounts.user.key(),
+        reason,
+        x_token_amount: amount,
+        amount: what,
+    });
+
+    emit!(PriceChange {
+        old_step_per_xstep_e18: old_price.scaled(Rounding::Down)?,
+        new_step_per_xstep_e18: new_price.scaled(Rounding::Down)?,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface;
+
+use crate::StakingAccount;
+
+#[derive(Accounts)]
+pub struct FundRewards<'info> {
+    pub funder: Signer<'info>,
+
+    pub stake_token_mint: Box<InterfaceAccount<'info, token_interface::Mint>>,
+
+    #[account(
+        seeds = [ crate::constants::STAKING_PDA_SEED, stake_token_mint.key().as_ref() ],
+        bump,
+    )]
+    pub staking: Account<'info, StakingAccount>,
+
+    /// the token account to fund from
+    #[account(
+        mut,
+        token::mint = stake_token_mint,
+        token::authority = funder,
+    )]
+    pub token_from: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [ crate::constants::REWARD_RESERVE_SEED, stake_token_mint.key().as_ref() ],
+        bump,
+    )]
+    pub reward_reserve: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    pub token_program: Interface<'info, token_interface::TokenInterface>,
+}
+
+pub fn fund_rewards(ctx: Context<FundRewards>, amount: u64) -> Result<()> {
+    let cpi_ctx = CpiContext::new(
+        ctx.accounts.token_program.to_account_info(),
+        token_interface::TransferChecked {
+            from: ctx.accounts.token_from.to_account_info(),
+            mint: ctx.accounts.stake_token_mint.to_account_info(),
+            to: ctx.accounts.reward_reserve.to_account_info(),
+            authority: ctx.accounts.funder.to_account_info(),
+        },
+    );
+    token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.stake_token_mint.decimals)
+}
@@ -0,0 +1,90 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface;
+
+use crate::program::Staker;
+use crate::{LockTier, StakingAccount, DEFAULT_TIMELOCK_DELAY, MAX_LOCK_TIERS};
+
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    /// pays rent on the initializing accounts and becomes the pool admin
+    #[account(mut)]
+    pub initializer: Signer<'info>,
+
+    pub stake_token_mint: Box<InterfaceAccount<'info, token_interface::Mint>>,
+
+    /// the not-yet-created, derived token vault
+    #[account(
+        init,
+        payer = initializer,
+        token::mint = stake_token_mint,
+        token::authority = stake_token_vault, //the PDA address is both the vault account and the authority
+        seeds = [ stake_token_mint.key().as_ref() ],
+        bump,
+    )]
+    pub stake_token_vault: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    #[account(
+        init,
+        payer = initializer,
+        seeds = [ crate::constants::STAKING_PDA_SEED, stake_token_mint.key().as_ref() ],
+        bump,
+        space = StakingAccount::LEN,
+    )]
+    pub staking: Account<'info, StakingAccount>,
+
+    /// the pool x token mint, the vault is its mint authority
+    #[account(
+        init,
+        payer = initializer,
+        seeds = [ crate::constants::X_TOKEN_MINT_SEED, stake_token_mint.key().as_ref() ],
+        bump,
+        mint::decimals = stake_token_mint.decimals,
+        mint::authority = stake_token_vault,
+        mint::token_program = x_token_program,
+    )]
+    pub x_token_mint: Box<InterfaceAccount<'info, token_interface::Mint>>,
+
+    /// the admin funded reserve rewards are dripped from
+    #[account(
+        init,
+        payer = initializer,
+        token::mint = stake_token_mint,
+        token::authority = stake_token_vault,
+        seeds = [ crate::constants::REWARD_RESERVE_SEED, stake_token_mint.key().as_ref() ],
+        bump,
+    )]
+    pub reward_reserve: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    /// holds the tokens of unstake requests until they are withdrawn
+    #[account(
+        init,
+        payer = initializer,
+        token::mint = stake_token_mint,
+        token::authority = stake_token_vault,
+        seeds = [ crate::constants::UNBONDING_VAULT_SEED, stake_token_mint.key().as_ref() ],
+        bump,
+    )]
+    pub unbonding_vault: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    /// only the upgrade authority of this program may initialize the pool
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, Staker>,
+
+    #[account(constraint = program_data.upgrade_authority_address == Some(initializer.key()))]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, token_interface::TokenInterface>,
+    /// SPL Token or Token-2022, the x token mint is created under it
+    pub x_token_program: Interface<'info, token_interface::TokenInterface>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+pub fn initialize(ctx: Context<Initialize>, lock_tiers: [LockTier; MAX_LOCK_TIERS]) -> Result<()> {
+    ctx.accounts.staking.initializer_key = ctx.accounts.initializer.key();
+    ctx.accounts.staking.stake_token_mint = ctx.accounts.stake_token_mint.key();
+    ctx.accounts.staking.x_token_mint = ctx.accounts.x_token_mint.key();
+    ctx.accounts.staking.last_emission_ts = Clock::get()?.unix_timestamp as u64;
+    ctx.accounts.staking.timelock_delay = DEFAULT_TIMELOCK_DELAY;
+    ctx.accounts.staking.set_lock_tiers(lock_tiers)
+}
@@ -1,5 +1,31 @@
+pub mod admin;
+pub mod close_position;
+pub mod crank;
+pub mod emergency;
+pub mod force_exit;
+pub mod fund_rewards;
+pub mod initialize;
+pub mod release;
+pub mod rewards;
 pub mod stake;
+pub mod sync_vault;
+pub mod timelock;
+pub mod unbonding;
 pub mod unstake;
+pub mod unstake_early;
 
+pub use admin::*;
+pub use close_position::*;
+pub use crank::*;
+pub use emergency::*;
+pub use force_exit::*;
+pub use fund_rewards::*;
+pub use initialize::*;
+pub use release::*;
+pub use rewards::*;
 pub use stake::*;
+pub use sync_vault::*;
+pub use timelock::*;
+pub use unbonding::*;
 pub use unstake::*;
+pub use unstake_early::*;
@@ -0,0 +1,172 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface;
+
+use crate::instructions::{accrue_rewards, compound_emission};
+use crate::{ErrorCode, StakingAccount, UserStakingAccount, PAUSE_UNSTAKE};
+
+#[derive(Accounts)]
+pub struct Release<'info> {
+    pub user: Signer<'info>,
+
+    #[account(address = staking.stake_token_mint)]
+    pub stake_token_mint: Box<InterfaceAccount<'info, token_interface::Mint>>,
+
+    /// the vault is the authority of every position escrow
+    #[account(
+        mut,
+        seeds = [ staking.stake_token_mint.as_ref() ],
+        bump,
+    )]
+    pub stake_token_vault: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [ crate::constants::STAKING_PDA_SEED, staking.stake_token_mint.as_ref() ],
+        bump,
+        constraint = !staking.is_paused(PAUSE_UNSTAKE) @ ErrorCode::Paused,
+        constraint = !staking.emergency_mode @ ErrorCode::EmergencyMode,
+    )]
+    pub staking: Account<'info, StakingAccount>,
+
+    /// the reserve rewards are dripped from
+    #[account(
+        mut,
+        seeds = [ crate::constants::REWARD_RESERVE_SEED, staking.stake_token_mint.as_ref() ],
+        bump,
+    )]
+    pub reward_reserve: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [ staking.key().as_ref(), user.key().as_ref() ],
+        bump,
+    )]
+    pub user_staking: Account<'info, UserStakingAccount>,
+
+    #[account(
+        mut,
+        address = staking.x_token_mint,
+    )]
+    pub x_token_mint: Box<InterfaceAccount<'info, token_interface::Mint>>,
+
+    #[account(
+        mut,
+        seeds = [ crate::constants::POSITION_ESCROW_SEED, user_staking.key().as_ref() ],
+        bump,
+    )]
+    pub position_escrow: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    /// the x token account receiving the released shares
+    #[account(
+        mut,
+        token::mint = x_token_mint,
+        token::authority = user,
+        token::token_program = x_token_program,
+    )]
+    pub user_x_token_account: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    pub token_program: Interface<'info, token_interface::TokenInterface>,
+    pub x_token_program: Interface<'info, token_interface::TokenInterface>,
+}
+
+/// compounds the position's emission, then moves unlocked shares from the
+/// position escrow into the user's wallet, where they can be transferred and
+/// unstaked but no longer earn rewards
+pub fn release(ctx: Context<Release>, amount: u64) -> Result<()> {
+    require!(amount > 0, ErrorCode::ZeroAmount);
+
+    let now_ts = Clock::get()?.unix_timestamp as u64;
+    let lock_end_date = ctx.accounts.user_staking.lock_end_date;
+    if now_ts < lock_end_date {
+        msg!("Locked for {} more seconds", lock_end_date - now_ts);
+        return err!(ErrorCode::NotExceedLockEndDate);
+    }
+
+    accrue_rewards(
+        &mut ctx.accounts.staking,
+        &ctx.accounts.stake_token_mint,
+        &mut ctx.accounts.reward_reserve,
+        &mut ctx.accounts.stake_token_vault,
+        &ctx.accounts.token_program,
+        ctx.bumps.stake_token_vault,
+    )?;
+    compound_emission(
+        &mut ctx.accounts.staking,
+        &mut ctx.accounts.user_staking,
+        &mut ctx.accounts.x_token_mint,
+        &mut ctx.accounts.position_escrow,
+        &ctx.accounts.stake_token_vault,
+        &ctx.accounts.x_token_program,
+        ctx.bumps.stake_token_vault,
+    )?;
+
+    let escrowed = ctx.accounts.position_escrow.amount;
+    require!(escrowed >= amount, ErrorCode::InsufficientShares);
+
+    ctx.accounts
+        .user_staking
+        .remove_shares(&mut ctx.accounts.staking, amount, escrowed, now_ts)?;
+
+    transfer_from_escrow(
+        &ctx.accounts.position_escrow,
+        ctx.accounts.user_x_token_account.to_account_info(),
+        &ctx.accounts.x_token_mint,
+        &ctx.accounts.stake_token_vault,
+        &ctx.accounts.x_token_program,
+        ctx.bumps.stake_token_vault,
+        amount,
+    )
+}
+
+/// moves shares out of a position escrow, the vault signs for the escrow
+pub fn transfer_from_escrow<'info>(
+    position_escrow: &InterfaceAccount<'info, token_interface::TokenAccount>,
+    to: AccountInfo<'info>,
+    x_token_mint: &InterfaceAccount<'info, token_interface::Mint>,
+    stake_token_vault: &InterfaceAccount<'info, token_interface::TokenAccount>,
+    x_token_program: &Interface<'info, token_interface::TokenInterface>,
+    vault_bump: u8,
+    amount: u64,
+) -> Result<()> {
+    //compute vault signer seeds
+    let stake_token_mint_key = stake_token_vault.mint;
+    let seeds = &[stake_token_mint_key.as_ref(), &[vault_bump]];
+    let signer = &[&seeds[..]];
+
+    let cpi_ctx = CpiContext::new_with_signer(
+        x_token_program.to_account_info(),
+        token_interface::TransferChecked {
+            from: position_escrow.to_account_info(),
+            mint: x_token_mint.to_account_info(),
+            to,
+            authority: stake_token_vault.to_account_info(),
+        },
+        signer,
+    );
+    token_interface::transfer_checked(cpi_ctx, amount, x_token_mint.decimals)
+}
+
+/// closes an emptied position escrow, refunding its rent to `destination`
+pub fn close_escrow<'info>(
+    position_escrow: &InterfaceAccount<'info, token_interface::TokenAccount>,
+    destination: AccountInfo<'info>,
+    stake_token_vault: &InterfaceAccount<'info, token_interface::TokenAccount>,
+    x_token_program: &Interface<'info, token_interface::TokenInterface>,
+    vault_bump: u8,
+) -> Result<()> {
+    //compute vault signer seeds
+    let stake_token_mint_key = stake_token_vault.mint;
+    let seeds = &[stake_token_mint_key.as_ref(), &[vault_bump]];
+    let signer = &[&seeds[..]];
+
+    let cpi_ctx = CpiContext::new_with_signer(
+        x_token_program.to_account_info(),
+        token_interface::CloseAccount {
+            account: position_escrow.to_account_info(),
+            destination,
+            authority: stake_token_vault.to_account_info(),
+        },
+        signer,
+    );
+    token_interface::close_account(cpi_ctx)
+}
@@ -0,0 +1,180 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Mint, Token, TokenAccount};
+use anchor_spl::token_interface;
+
+use crate::math::SafeMath;
+use crate::{
+    ErrorCode, RewardClaimed, RewardInfo, StakingAccount, UserStakingAccount, MAX_REWARDS,
+    PAUSE_CLAIM,
+};
+
+#[derive(Accounts)]
+pub struct AddReward<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [ crate::constants::STAKING_PDA_SEED, staking.stake_token_mint.as_ref() ],
+        bump,
+        constraint = staking.is_parameter_manager(authority.key) @ ErrorCode::Unauthorized,
+    )]
+    pub staking: Account<'info, StakingAccount>,
+
+    /// the vault is the authority of every reward vault
+    #[account(
+        seeds = [ staking.stake_token_mint.as_ref() ],
+        bump,
+    )]
+    pub stake_token_vault: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    pub reward_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        init,
+        payer = authority,
+        token::mint = reward_mint,
+        token::authority = stake_token_vault,
+        seeds = [ crate::constants::REWARD_VAULT_SEED, staking.key().as_ref(), reward_mint.key().as_ref() ],
+        bump,
+    )]
+    pub reward_vault: Box<Account<'info, TokenAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+/// registers a reward that emits nothing yet, its rate is set with a
+/// timelocked ParameterChange::RewardRate
+pub fn add_reward(ctx: Context<AddReward>) -> Result<()> {
+    let now_ts = Clock::get()?.unix_timestamp as u64;
+    let reward_mint = ctx.accounts.reward_mint.key();
+    let staking = &mut ctx.accounts.staking;
+
+    require!(
+        staking
+            .rewards
+            .iter()
+            .all(|reward| reward.mint != reward_mint),
+        ErrorCode::DuplicateReward
+    );
+
+    staking.update_rewards(now_ts)?;
+
+    let slot = staking
+        .rewards
+        .iter_mut()
+        .find(|reward| reward.mint == Pubkey::default())
+        .ok_or(ErrorCode::RewardSlotsFull)?;
+    *slot = RewardInfo {
+        mint: reward_mint,
+        vault: ctx.accounts.reward_vault.key(),
+        rate: 0,
+        reward_per_share: 0,
+        last_update_ts: now_ts,
+    };
+
+    Ok(())
+}
+
+/// remaining accounts: for every registered reward, in slot order,
+/// the reward vault followed by the user token account to pay into
+#[derive(Accounts)]
+pub struct ClaimRewards<'info> {
+    pub user: Signer<'info>,
+
+    pub stake_token_mint: Box<InterfaceAccount<'info, token_interface::Mint>>,
+
+    #[account(
+        seeds = [ stake_token_mint.key().as_ref() ],
+        bump,
+    )]
+    pub stake_token_vault: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [ crate::constants::STAKING_PDA_SEED, stake_token_mint.key().as_ref() ],
+        bump,
+        constraint = !staking.is_paused(PAUSE_CLAIM) @ ErrorCode::Paused,
+    )]
+    pub staking: Account<'info, StakingAccount>,
+
+    #[account(
+        mut,
+        seeds = [ staking.key().as_ref(), user.key().as_ref() ],
+        bump,
+    )]
+    pub user_staking: Account<'info, UserStakingAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+pub fn claim_rewards<'info>(ctx: Context<'_, '_, 'info, 'info, ClaimRewards<'info>>) -> Result<()> {
+    let now_ts = Clock::get()?.unix_timestamp as u64;
+
+    ctx.accounts.staking.update_rewards(now_ts)?;
+    ctx.accounts
+        .user_staking
+        .settle_rewards(&ctx.accounts.staking)?;
+    ctx.accounts
+        .user_staking
+        .reset_reward_debts(&ctx.accounts.staking)?;
+
+    //compute vault signer seeds
+    let stake_token_mint_key = ctx.accounts.stake_token_mint.key();
+    let seeds = &[
+        stake_token_mint_key.as_ref(),
+        &[ctx.bumps.stake_token_vault],
+    ];
+    let signer = &[&seeds[..]];
+
+    let remaining_accounts = &mut ctx.remaining_accounts.iter();
+
+    for i in 0..MAX_REWARDS {
+        let reward = ctx.accounts.staking.rewards[i];
+        if reward.mint == Pubkey::default() {
+            continue;
+        }
+
+        let reward_vault_info = next_account_info(remaining_accounts)?;
+        let token_to_info = next_account_info(remaining_accounts)?;
+
+        require_keys_eq!(
+            reward_vault_info.key(),
+            reward.vault,
+            ErrorCode::InvalidRewardAccount
+        );
+        let reward_vault = Account::<TokenAccount>::try_from(reward_vault_info)?;
+        let token_to = Account::<TokenAccount>::try_from(token_to_info)?;
+        require_keys_eq!(token_to.mint, reward.mint, ErrorCode::InvalidRewardAccount);
+
+        //an underfunded reward vault keeps the rest owed
+        let amount = ctx.accounts.user_staking.rewards_owed[i].min(reward_vault.amount);
+        if amount == 0 {
+            continue;
+        }
+        ctx.accounts.user_staking.rewards_owed[i] =
+            ctx.accounts.user_staking.rewards_owed[i].safe_sub(amount)?;
+
+        //transfer from reward vault to user
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            token::Transfer {
+                from: reward_vault_info.clone(),
+                to: token_to_info.clone(),
+                authority: ctx.accounts.stake_token_vault.to_account_info(),
+            },
+            signer,
+        );
+        token::transfer(cpi_ctx, amount)?;
+
+        emit!(RewardClaimed {
+            user: ctx.accounts.user.key(),
+            mint: reward.mint,
+            amount,
+        });
+    }
+
+    Ok(())
+}
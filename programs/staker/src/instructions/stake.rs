@@ -1,31 +1,21 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{Mint, Token, TokenAccount};
-
-#[account]
-#[derive(Default)]
-pub struct StakingAccount {
-    pub initializer_key: Pubkey,
-    pub lock_end_date: u64,
-    pub total_x_token: u64,
-    pub freeze_program: bool,
-}
+use anchor_spl::token_interface;
 
-#[account]
-#[derive(Default)]
-pub struct UserStakingAccount {
-    pub amount: u64,
-    pub x_token_amount: u64,
-}
+use crate::instructions::{accrue_rewards, compound_emission};
+use crate::math::{mul_div, ExchangeRate, Rounding, SafeMath};
+use crate::{
+    ErrorCode, LockTier, PriceChange, StakeFee, StakedFor, StakingAccount, UserStakingAccount,
+    PAUSE_STAKE,
+};
 
+/// the accounts every deposit is funded and minted through
 #[derive(Accounts)]
-pub struct Stake<'info> {
+pub struct Deposit<'info> {
+    /// funds the deposit and pays rent
     #[account(mut)]
     pub user: Signer<'info>,
 
-    #[account(
-        address = crate::constants::PLENTY_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap(),
-    )]
-    pub stake_token_mint: Box<Account<'info, Mint>>,
+    pub stake_token_mint: Box<InterfaceAccount<'info, token_interface::Mint>>,
 
     /// the token account to withdraw from
     #[account(
@@ -33,185 +23,314 @@ pub struct Stake<'info> {
         token::mint = stake_token_mint,
         token::authority = user,
     )]
-    pub stake_token_account: Box<Account<'info, TokenAccount>>,
+    pub stake_token_account: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
 
     /// the token vault to stake
     #[account(
-        init_if_needed,
-        payer = user,
+        mut,
+        seeds = [ stake_token_mint.key().as_ref() ],
+        bump,
+    )]
+    pub stake_token_vault: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [ crate::constants::STAKING_PDA_SEED, stake_token_mint.key().as_ref() ],
+        bump,
+        constraint = !staking.is_paused(PAUSE_STAKE) @ ErrorCode::Paused,
+        constraint = !staking.emergency_mode @ ErrorCode::EmergencyMode,
+    )]
+    pub staking: Account<'info, StakingAccount>,
+
+    /// the reserve rewards are dripped from
+    #[account(
+        mut,
+        seeds = [ crate::constants::REWARD_RESERVE_SEED, stake_token_mint.key().as_ref() ],
+        bump,
+    )]
+    pub reward_reserve: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    #[account(
+        mut,
+        address = staking.x_token_mint,
+    )]
+    pub x_token_mint: Box<InterfaceAccount<'info, token_interface::Mint>>,
+
+    /// stake fee account, owned by the fee receiver
+    #[account(
+        mut,
         token::mint = stake_token_mint,
-        token::authority = stake_token_vault,
+        token::authority = crate::constants::STAKE_FEE_RECEIVER.parse::<Pubkey>().unwrap(),
+    )]
+    pub stake_fee: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    pub token_program: Interface<'info, token_interface::TokenInterface>,
+    pub x_token_program: Interface<'info, token_interface::TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct Stake<'info> {
+    pub deposit: Deposit<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = deposit.user,
+        seeds = [ deposit.staking.key().as_ref(), deposit.user.key().as_ref() ],
+        bump,
+        space = UserStakingAccount::LEN,
     )]
-    pub stake_token_vault: Box<Account<'info, TokenAccount>>,
+    pub user_staking: Account<'info, UserStakingAccount>,
 
+    /// holds the minted x tokens until the position lock ends
     #[account(
         init_if_needed,
-        payer = user,
-        seeds = [ crates::constants::STAKING_SEED.as_ref() ],
+        payer = deposit.user,
+        seeds = [ crate::constants::POSITION_ESCROW_SEED, user_staking.key().as_ref() ],
         bump,
-        space = StakingAccount::Len,
-        constraint = !staking.freeze_program,
+        token::mint = deposit.x_token_mint,
+        token::authority = deposit.stake_token_vault,
+        token::token_program = deposit.x_token_program,
     )]
-    pub staking: Account<'info, StakingAccount>,
+    pub position_escrow: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct StakeFor<'info> {
+    pub deposit: Deposit<'info>,
+
+    /// CHECK: owns the position the deposit is staked into
+    pub beneficiary: UncheckedAccount<'info>,
 
     #[account(
         init_if_needed,
-        payer = user,
-        seeds = [ user.key().as_ref() ],
+        payer = deposit.user,
+        seeds = [ deposit.staking.key().as_ref(), beneficiary.key().as_ref() ],
         bump,
         space = UserStakingAccount::LEN,
     )]
     pub user_staking: Account<'info, UserStakingAccount>,
 
-    /// stake fee account
+    /// holds the minted x tokens until the position lock ends
     #[account(
-        mut,
-        address = crate::constants::STAKE_FEE_RECEIVER.parse::<Pubkey>().unwrap(),
+        init_if_needed,
+        payer = deposit.user,
+        seeds = [ crate::constants::POSITION_ESCROW_SEED, user_staking.key().as_ref() ],
+        bump,
+        token::mint = deposit.x_token_mint,
+        token::authority = deposit.stake_token_vault,
+        token::token_program = deposit.x_token_program,
     )]
-    pub stake_fee: Box<Account<'info, TokenAccount>>,
+    pub position_escrow: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
 
     pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>,
     pub rent: Sysvar<'info, Rent>,
 }
 
-// #[derive(Accounts)]
-// #[instruction(_nonce_vault: u8, _nonce_staking: u8, _nonce_user_staking: u8)]
-// pub struct Stake<'info> {
-//     #[account(
-//         address = constants::STEP_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap(),
-//     )]
-//     pub token_mint: Box<Account<'info, Mint>>,
-
-//     #[account(mut)]
-//     //the token account to withdraw from
-//     pub token_from: Box<Account<'info, TokenAccount>>,
-
-//     //the authority allowed to transfer from token_from
-//     pub token_from_authority: Signer<'info>,
-
-//     #[account(
-//         mut,
-//         seeds = [ token_mint.key().as_ref() ],
-//         bump = _nonce_vault,
-//     )]
-//     pub token_vault: Box<Account<'info, TokenAccount>>,
-
-//     #[account(
-//         mut,
-//         seeds = [ constants::STAKING_PDA_SEED.as_ref() ],
-//         bump = _nonce_staking,
-//         constraint = !staking_account.freeze_program,
-//     )]
-//     pub staking_account: ProgramAccount<'info, StakingAccount>,
-
-//     #[account(
-//         init_if_needed,
-//         payer = token_from_authority,
-//         seeds = [ token_from_authority.key().as_ref() ],
-//         bump = _nonce_user_staking,
-//     )]
-//     pub user_staking_account: ProgramAccount<'info, UserStakingAccount>,
-
-//     pub system_program: Program<'info, System>,
-//     pub token_program: Program<'info, Token>,
-//     pub rent: Sysvar<'info, Rent>,
-// }
-
-pub fn stake(ctx: Context<Stake>, stake_amount: u64) -> Result<()> {
-    let total_token = ctx.accounts.stake_token_vault.amount;
-    let total_x_token = ctx.accounts.staking_account.total_x_token;
-    let old_price = get_price(&ctx.accounts.token_vault, &ctx.accounts.staking_account);
+pub fn stake(
+    ctx: Context<Stake>,
+    stake_amount: u64,
+    lock_tier: u8,
+    min_shares_out: u64,
+) -> Result<()> {
+    process_stake(
+        &mut ctx.accounts.deposit,
+        &ctx.bumps.deposit,
+        &mut ctx.accounts.user_staking,
+        &mut ctx.accounts.position_escrow,
+        stake_amount,
+        lock_tier,
+        true,
+        min_shares_out,
+    )?;
 
-    // mint x tokens
-    if total_token == 0 || total_x_token == 0 {
-        ctx.accounts.staking_account.total_x_token = (ctx.accounts.staking_account.total_x_token
-            as u128)
-            .checked_add(amount as u128)
-            .unwrap()
-            .try_into()
-            .unwrap();
-        ctx.accounts.user_staking_account.x_token_amount =
-            (ctx.accounts.user_staking_account.x_token_amount as u128)
-                .checked_add(amount as u128)
-                .unwrap()
-                .try_into()
-                .unwrap();
-    } else {
-        let what: u64 = (amount as u128)
-            .checked_mul(total_x_token as u128)
-            .unwrap()
-            .checked_div(total_token as u128)
-            .unwrap()
-            .try_into()
-            .unwrap();
-
-        ctx.accounts.staking_account.total_x_token = (ctx.accounts.staking_account.total_x_token
-            as u128)
-            .checked_add(what as u128)
-            .unwrap()
-            .try_into()
-            .unwrap();
-        ctx.accounts.user_staking_account.x_token_amount =
-            (ctx.accounts.user_staking_account.x_token_amount as u128)
-                .checked_add(what as u128)
-                .unwrap()
-                .try_into()
-                .unwrap();
-    }
+    Ok(())
+}
+
+/// stakes the user's tokens into the beneficiary's position under the lock
+/// tier the payer chose. only the owner may extend the lock of shares already
+/// in the escrow, so the tier must not outlast the current lock of a funded position
+pub fn stake_for(
+    ctx: Context<StakeFor>,
+    stake_amount: u64,
+    lock_tier: u8,
+    min_shares_out: u64,
+) -> Result<()> {
+    let shares = process_stake(
+        &mut ctx.accounts.deposit,
+        &ctx.bumps.deposit,
+        &mut ctx.accounts.user_staking,
+        &mut ctx.accounts.position_escrow,
+        stake_amount,
+        lock_tier,
+        false,
+        min_shares_out,
+    )?;
+
+    emit!(StakedFor {
+        payer: ctx.accounts.deposit.user.key(),
+        beneficiary: ctx.accounts.beneficiary.key(),
+        stake_amount,
+        x_token_amount: shares,
+    });
+
+    Ok(())
+}
+
+/// mints the shares of a deposit into `position_escrow` and locks the position
+/// for `lock_tier`. an escrow that already holds shares only has its lock
+/// extended when `may_extend_lock` is set. returns the minted shares
+#[allow(clippy::too_many_arguments)]
+fn process_stake<'info>(
+    accounts: &mut Deposit<'info>,
+    bumps: &DepositBumps,
+    user_staking: &mut Account<'info, UserStakingAccount>,
+    position_escrow: &mut InterfaceAccount<'info, token_interface::TokenAccount>,
+    stake_amount: u64,
+    lock_tier: u8,
+    may_extend_lock: bool,
+    min_shares_out: u64,
+) -> Result<u64> {
+    let LockTier {
+        duration,
+        multiplier_bps,
+    } = *accounts
+        .staking
+        .lock_tiers
+        .get(lock_tier as usize)
+        .ok_or(ErrorCode::InvalidLockTier)?;
+    require!(multiplier_bps != 0, ErrorCode::InvalidLockTier);
+    require!(stake_amount > 0, ErrorCode::ZeroAmount);
+    let escrowed = position_escrow.amount;
+
+    accrue_rewards(
+        &mut accounts.staking,
+        &accounts.stake_token_mint,
+        &mut accounts.reward_reserve,
+        &mut accounts.stake_token_vault,
+        &accounts.token_program,
+        bumps.stake_token_vault,
+    )?;
+    compound_emission(
+        &mut accounts.staking,
+        user_staking,
+        &mut accounts.x_token_mint,
+        position_escrow,
+        &accounts.stake_token_vault,
+        &accounts.x_token_program,
+        bumps.stake_token_vault,
+    )?;
+
+    let old_price = get_price(&accounts.staking, &accounts.x_token_mint);
+
+    //split off the stake fee
+    let fee = mul_div(
+        stake_amount,
+        accounts.staking.stake_fee_bps as u64,
+        BPS_DENOMINATOR,
+    )?;
+    let stake_amount = stake_amount.safe_sub(fee)?;
 
     //transfer the users tokens to the vault
+    let vault_before = accounts.stake_token_vault.amount;
     let cpi_ctx = CpiContext::new(
-        ctx.accounts.token_program.to_account_info(),
-        token::Transfer {
-            from: ctx.accounts.token_from.to_account_info(),
-            to: ctx.accounts.token_vault.to_account_info(),
-            authority: ctx.accounts.token_from_authority.to_account_info(),
+        accounts.token_program.to_account_info(),
+        token_interface::TransferChecked {
+            from: accounts.stake_token_account.to_account_info(),
+            mint: accounts.stake_token_mint.to_account_info(),
+            to: accounts.stake_token_vault.to_account_info(),
+            authority: accounts.user.to_account_info(),
+        },
+    );
+    token_interface::transfer_checked(cpi_ctx, stake_amount, accounts.stake_token_mint.decimals)?;
+
+    //credit what the vault received, a transfer fee mint withholds part of it
+    accounts.stake_token_vault.reload()?;
+    let stake_amount = accounts.stake_token_vault.amount.safe_sub(vault_before)?;
+    accounts.staking.total_staked = accounts.staking.total_staked.safe_add(stake_amount)?;
+
+    // mint x tokens
+    let what = old_price.to_shares(stake_amount, Rounding::Down)?;
+    require!(what > 0, ErrorCode::ZeroAmount);
+    require!(what >= min_shares_out, ErrorCode::SlippageExceeded);
+
+    //compute vault signer seeds
+    let stake_token_mint_key = accounts.stake_token_mint.key();
+    let seeds = &[stake_token_mint_key.as_ref(), &[bumps.stake_token_vault]];
+    let signer = &[&seeds[..]];
+
+    let cpi_ctx = CpiContext::new_with_signer(
+        accounts.x_token_program.to_account_info(),
+        token_interface::MintTo {
+            mint: accounts.x_token_mint.to_account_info(),
+            to: position_escrow.to_account_info(),
+            authority: accounts.stake_token_vault.to_account_info(),
         },
+        signer,
     );
-    token::transfer(cpi_ctx, amount)?;
+    token_interface::mint_to(cpi_ctx, what)?;
+
+    //transfer the fee to the fee receiver
+    if fee > 0 {
+        let cpi_ctx = CpiContext::new(
+            accounts.token_program.to_account_info(),
+            token_interface::TransferChecked {
+                from: accounts.stake_token_account.to_account_info(),
+                mint: accounts.stake_token_mint.to_account_info(),
+                to: accounts.stake_fee.to_account_info(),
+                authority: accounts.user.to_account_info(),
+            },
+        );
+        token_interface::transfer_checked(cpi_ctx, fee, accounts.stake_token_mint.decimals)?;
+    }
+
+    accounts.x_token_mint.reload()?;
+    accounts
+        .staking
+        .check_backing(accounts.stake_token_vault.amount)?;
 
-    (&mut ctx.accounts.token_vault).reload()?;
+    //restart the lock of the position, never shortening it
+    let now_ts = Clock::get()?.unix_timestamp as u64;
+    let lock_end_date = now_ts.safe_add(duration)?;
+    if lock_end_date > user_staking.lock_end_date {
+        require!(
+            may_extend_lock || escrowed == 0,
+            ErrorCode::LockExtensionDenied
+        );
+        user_staking.lock_end_date = lock_end_date;
+    }
+    user_staking.lock_tier = lock_tier;
+
+    //longer locks boost the weight in emission and additional rewards, never the shares
+    let weight = mul_div(what, multiplier_bps as u64, BPS_DENOMINATOR)?;
+    user_staking.add_reward_shares(&mut accounts.staking, weight, now_ts)?;
 
     //plus user staking amount
-    ctx.accounts.user_staking_account.amount = (ctx.accounts.user_staking_account.amount as u128)
-        .checked_add(amount as u128)
-        .unwrap()
-        .try_into()
-        .unwrap();
+    user_staking.amount = user_staking.amount.safe_add(stake_amount)?;
+
+    let new_price = get_price(&accounts.staking, &accounts.x_token_mint);
 
-    let new_price = get_price(&ctx.accounts.token_vault, &ctx.accounts.staking_account);
+    emit!(StakeFee {
+        user: accounts.user.key(),
+        stake_amount,
+        fee,
+    });
 
     emit!(PriceChange {
-        old_step_per_xstep_e9: old_price.0,
-        old_step_per_xstep: old_price.1,
-        new_step_per_xstep_e9: new_price.0,
-        new_step_per_xstep: new_price.1,
+        old_step_per_xstep_e18: old_price.scaled(Rounding::Down)?,
+        new_step_per_xstep_e18: new_price.scaled(Rounding::Down)?,
     });
 
-    Ok(())
+    Ok(what)
 }
 
-const E9: u128 = 1000000000;
-
-pub fn get_price<'info>(
-    vault: &Account<'info, TokenAccount>,
-    staking: &Account<'info, StakingAccount>,
-) -> (u64, String) {
-    let total_token = vault.amount;
-    let total_x_token = staking.total_x_token;
-
-    if total_x_token == 0 {
-        return (0, String::from("0"));
-    }
+pub const BPS_DENOMINATOR: u64 = 10000;
 
-    let price_uint = (total_token as u128)
-        .checked_mul(E9 as u128)
-        .unwrap()
-        .checked_div(total_x_token as u128)
-        .unwrap()
-        .try_into()
-        .unwrap();
-    let price_float = (total_token as f64) / (total_x_token as f64);
-    return (price_uint, price_float.to_string());
+pub fn get_price(
+    staking: &StakingAccount,
+    x_token_mint: &InterfaceAccount<token_interface::Mint>,
+) -> ExchangeRate {
+    ExchangeRate::new(staking.total_staked, x_token_mint.supply)
 }
@@ -0,0 +1,110 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface;
+
+use crate::math::SafeMath;
+use crate::{ErrorCode, StakingAccount, VaultSkimmed, VaultSynced, PAUSE_ADMIN_WITHDRAW};
+
+/// folds tokens sent straight to the vault into the accounted balance,
+/// paying them out to the current stakers
+#[derive(Accounts)]
+pub struct SyncVault<'info> {
+    pub initializer: Signer<'info>,
+
+    pub stake_token_mint: Box<InterfaceAccount<'info, token_interface::Mint>>,
+
+    #[account(
+        seeds = [ stake_token_mint.key().as_ref() ],
+        bump,
+    )]
+    pub stake_token_vault: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [ crate::constants::STAKING_PDA_SEED, stake_token_mint.key().as_ref() ],
+        bump,
+        constraint = staking.initializer_key == *initializer.key @ ErrorCode::Unauthorized,
+    )]
+    pub staking: Account<'info, StakingAccount>,
+}
+
+pub fn sync_vault(ctx: Context<SyncVault>) -> Result<()> {
+    let staking = &mut ctx.accounts.staking;
+    let vault_amount = ctx.accounts.stake_token_vault.amount;
+    let surplus = staking.surplus(vault_amount)?;
+    staking.total_staked = staking.total_staked.safe_add(surplus)?;
+
+    emit!(VaultSynced { amount: surplus });
+
+    Ok(())
+}
+
+/// returns tokens sent straight to the vault without touching the exchange rate
+#[derive(Accounts)]
+pub struct Skim<'info> {
+    pub initializer: Signer<'info>,
+
+    pub stake_token_mint: Box<InterfaceAccount<'info, token_interface::Mint>>,
+
+    #[account(
+        mut,
+        seeds = [ stake_token_mint.key().as_ref() ],
+        bump,
+    )]
+    pub stake_token_vault: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    #[account(
+        seeds = [ crate::constants::STAKING_PDA_SEED, stake_token_mint.key().as_ref() ],
+        bump,
+        constraint = staking.initializer_key == *initializer.key @ ErrorCode::Unauthorized,
+        constraint = !staking.is_paused(PAUSE_ADMIN_WITHDRAW) @ ErrorCode::Paused,
+    )]
+    pub staking: Account<'info, StakingAccount>,
+
+    #[account(
+        mut,
+        token::mint = stake_token_mint,
+    )]
+    //the token account to send the surplus to
+    pub token_to: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    pub token_program: Interface<'info, token_interface::TokenInterface>,
+}
+
+pub fn skim(ctx: Context<Skim>) -> Result<()> {
+    let staking = &ctx.accounts.staking;
+    let vault_amount = ctx.accounts.stake_token_vault.amount;
+    let surplus = staking.surplus(vault_amount)?;
+
+    if surplus > 0 {
+        //compute vault signer seeds
+        let stake_token_mint_key = ctx.accounts.stake_token_mint.key();
+        let seeds = &[
+            stake_token_mint_key.as_ref(),
+            &[ctx.bumps.stake_token_vault],
+        ];
+        let signer = &[&seeds[..]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            token_interface::TransferChecked {
+                from: ctx.accounts.stake_token_vault.to_account_info(),
+                mint: ctx.accounts.stake_token_mint.to_account_info(),
+                to: ctx.accounts.token_to.to_account_info(),
+                authority: ctx.accounts.stake_token_vault.to_account_info(),
+            },
+            signer,
+        );
+        token_interface::transfer_checked(
+            cpi_ctx,
+            surplus,
+            ctx.accounts.stake_token_mint.decimals,
+        )?;
+    }
+
+    emit!(VaultSkimmed {
+        amount: surplus,
+        token_to: ctx.accounts.token_to.key(),
+    });
+
+    Ok(())
+}
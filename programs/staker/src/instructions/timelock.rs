@@ -0,0 +1,132 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    ChangeCancelled, ChangeExecuted, ChangeQueued, ErrorCode, ParameterChange, PendingChange,
+    StakingAccount,
+};
+
+#[derive(Accounts)]
+#[instruction(change: ParameterChange)]
+pub struct QueueChange<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [ crate::constants::STAKING_PDA_SEED, staking.stake_token_mint.as_ref() ],
+        bump,
+        constraint = staking.may_change(&change, authority.key) @ ErrorCode::Unauthorized,
+    )]
+    pub staking: Account<'info, StakingAccount>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = PendingChange::LEN,
+        seeds = [ crate::constants::PENDING_CHANGE_SEED, staking.key().as_ref(), &[change.kind()] ],
+        bump,
+    )]
+    pub pending_change: Account<'info, PendingChange>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn queue_change(ctx: Context<QueueChange>, change: ParameterChange) -> Result<()> {
+    let now_ts = Clock::get()?.unix_timestamp as u64;
+    let staking = &ctx.accounts.staking;
+
+    //reject changes that could never be executed
+    staking.validate_change(&change)?;
+
+    let eta = now_ts.saturating_add(staking.timelock_delay);
+
+    let pending_change = &mut ctx.accounts.pending_change;
+    pending_change.staking = staking.key();
+    pending_change.proposer = ctx.accounts.authority.key();
+    pending_change.change = change;
+    pending_change.eta = eta;
+
+    emit!(ChangeQueued {
+        pending_change: pending_change.key(),
+        change,
+        eta,
+    });
+
+    Ok(())
+}
+
+/// anyone may execute a change once its timelock has elapsed
+#[derive(Accounts)]
+pub struct ExecuteChange<'info> {
+    #[account(
+        mut,
+        seeds = [ crate::constants::STAKING_PDA_SEED, staking.stake_token_mint.as_ref() ],
+        bump,
+    )]
+    pub staking: Account<'info, StakingAccount>,
+
+    #[account(
+        mut,
+        close = proposer,
+        has_one = staking,
+        has_one = proposer,
+    )]
+    pub pending_change: Account<'info, PendingChange>,
+
+    /// CHECK: receives the pending change rent, checked by has_one
+    #[account(mut)]
+    pub proposer: UncheckedAccount<'info>,
+}
+
+pub fn execute_change(ctx: Context<ExecuteChange>) -> Result<()> {
+    let now_ts = Clock::get()?.unix_timestamp as u64;
+    let pending_change = &ctx.accounts.pending_change;
+
+    if now_ts < pending_change.eta {
+        msg!("Executable in {} seconds", pending_change.eta - now_ts);
+        return err!(ErrorCode::TimelockNotElapsed);
+    }
+
+    ctx.accounts
+        .staking
+        .apply_change(&pending_change.change, now_ts)?;
+
+    emit!(ChangeExecuted {
+        pending_change: pending_change.key(),
+        change: pending_change.change,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct CancelChange<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [ crate::constants::STAKING_PDA_SEED, staking.stake_token_mint.as_ref() ],
+        bump,
+        constraint = staking.may_change(&pending_change.change, authority.key) @ ErrorCode::Unauthorized,
+    )]
+    pub staking: Account<'info, StakingAccount>,
+
+    #[account(
+        mut,
+        close = proposer,
+        has_one = staking,
+        has_one = proposer,
+    )]
+    pub pending_change: Account<'info, PendingChange>,
+
+    /// CHECK: receives the pending change rent, checked by has_one
+    #[account(mut)]
+    pub proposer: UncheckedAccount<'info>,
+}
+
+pub fn cancel_change(ctx: Context<CancelChange>) -> Result<()> {
+    emit!(ChangeCancelled {
+        pending_change: ctx.accounts.pending_change.key(),
+        change: ctx.accounts.pending_change.change,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,338 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface;
+
+use crate::instructions::{accrue_rewards, get_price, BPS_DENOMINATOR};
+use crate::math::{mul_div, Rounding, SafeMath};
+use crate::{
+    ErrorCode, PriceChange, StakingAccount, UnstakeRequest, UnstakeRequested, UserStakingAccount,
+    Withdrawn, PAUSE_UNSTAKE,
+};
+
+#[derive(Accounts)]
+pub struct RequestUnstake<'info> {
+    pub stake_token_mint: Box<InterfaceAccount<'info, token_interface::Mint>>,
+
+    //the authority allowed to burn from user_x_token_account
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [ stake_token_mint.key().as_ref() ],
+        bump,
+    )]
+    pub stake_token_vault: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [ crate::constants::STAKING_PDA_SEED, stake_token_mint.key().as_ref() ],
+        bump,
+        constraint = !staking.is_paused(PAUSE_UNSTAKE) @ ErrorCode::Paused,
+        constraint = !staking.emergency_mode @ ErrorCode::EmergencyMode,
+    )]
+    pub staking: Account<'info, StakingAccount>,
+
+    /// the reserve rewards are dripped from
+    #[account(
+        mut,
+        seeds = [ crate::constants::REWARD_RESERVE_SEED, stake_token_mint.key().as_ref() ],
+        bump,
+    )]
+    pub reward_reserve: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    /// holds the tokens of requests that have not been withdrawn yet
+    #[account(
+        mut,
+        seeds = [ crate::constants::UNBONDING_VAULT_SEED, stake_token_mint.key().as_ref() ],
+        bump,
+    )]
+    pub unbonding_vault: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    /// counts the requests of the user, created for holders of released or
+    /// transferred x tokens that have no position yet
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [ staking.key().as_ref(), user.key().as_ref() ],
+        bump,
+        space = UserStakingAccount::LEN,
+    )]
+    pub user_staking: Account<'info, UserStakingAccount>,
+
+    /// created along with a new position so it can be closed like any other
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [ crate::constants::POSITION_ESCROW_SEED, user_staking.key().as_ref() ],
+        bump,
+        token::mint = x_token_mint,
+        token::authority = stake_token_vault,
+        token::token_program = x_token_program,
+    )]
+    pub position_escrow: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    #[account(
+        init,
+        payer = user,
+        seeds = [
+            crate::constants::UNSTAKE_REQUEST_SEED,
+            user_staking.key().as_ref(),
+            &user_staking.unstake_request_count.to_le_bytes(),
+        ],
+        bump,
+        space = UnstakeRequest::LEN,
+    )]
+    pub unstake_request: Account<'info, UnstakeRequest>,
+
+    #[account(
+        mut,
+        address = staking.x_token_mint,
+    )]
+    pub x_token_mint: Box<InterfaceAccount<'info, token_interface::Mint>>,
+
+    /// the x token account to burn from
+    #[account(
+        mut,
+        token::mint = x_token_mint,
+        token::authority = user,
+        token::token_program = x_token_program,
+    )]
+    pub user_x_token_account: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, token_interface::TokenInterface>,
+    pub x_token_program: Interface<'info, token_interface::TokenInterface>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+pub fn request_unstake(
+    ctx: Context<RequestUnstake>,
+    amount: u64,
+    min_tokens_out: u64,
+) -> Result<()> {
+    require!(amount > 0, ErrorCode::ZeroAmount);
+
+    process_request_unstake(ctx.accounts, &ctx.bumps, amount, min_tokens_out, 0, None)?;
+
+    Ok(())
+}
+
+/// burns x tokens and parks their vault share in a new unstake request,
+/// minus a `penalty_bps` cut that goes to `penalty_to` or stays in the vault.
+/// returns the parked amount and the penalty
+pub fn process_request_unstake<'info>(
+    accounts: &mut RequestUnstake<'info>,
+    bumps: &RequestUnstakeBumps,
+    amount: u64,
+    min_tokens_out: u64,
+    penalty_bps: u64,
+    penalty_to: Option<AccountInfo<'info>>,
+) -> Result<(u64, u64)> {
+    require!(
+        accounts.user_x_token_account.amount >= amount,
+        ErrorCode::InsufficientShares
+    );
+
+    let now_ts = Clock::get()?.unix_timestamp;
+
+    accrue_rewards(
+        &mut accounts.staking,
+        &accounts.stake_token_mint,
+        &mut accounts.reward_reserve,
+        &mut accounts.stake_token_vault,
+        &accounts.token_program,
+        bumps.stake_token_vault,
+    )?;
+
+    let old_price = get_price(&accounts.staking, &accounts.x_token_mint);
+
+    //burn what is being sent
+    let cpi_ctx = CpiContext::new(
+        accounts.x_token_program.to_account_info(),
+        token_interface::Burn {
+            mint: accounts.x_token_mint.to_account_info(),
+            from: accounts.user_x_token_account.to_account_info(),
+            authority: accounts.user.to_account_info(),
+        },
+    );
+    token_interface::burn(cpi_ctx, amount)?;
+
+    //determine user share of vault
+    let what = old_price.to_tokens(amount, Rounding::Down)?;
+    let penalty = mul_div(what, penalty_bps, BPS_DENOMINATOR)?;
+    let what = what.safe_sub(penalty)?;
+    require!(what >= min_tokens_out, ErrorCode::SlippageExceeded);
+
+    //compute vault signer seeds
+    let stake_token_mint_key = accounts.stake_token_mint.key();
+    let seeds = &[stake_token_mint_key.as_ref(), &[bumps.stake_token_vault]];
+    let signer = &[&seeds[..]];
+
+    //park the user share outside of the vault so it stops earning
+    let unbonding_before = accounts.unbonding_vault.amount;
+    let cpi_ctx = CpiContext::new_with_signer(
+        accounts.token_program.to_account_info(),
+        token_interface::TransferChecked {
+            from: accounts.stake_token_vault.to_account_info(),
+            mint: accounts.stake_token_mint.to_account_info(),
+            to: accounts.unbonding_vault.to_account_info(),
+            authority: accounts.stake_token_vault.to_account_info(),
+        },
+        signer,
+    );
+    token_interface::transfer_checked(cpi_ctx, what, accounts.stake_token_mint.decimals)?;
+    accounts.staking.total_staked = accounts.staking.total_staked.safe_sub(what)?;
+
+    //a penalty without a receiver stays in the vault for the remaining stakers
+    if let Some(penalty_to) = penalty_to.filter(|_| penalty > 0) {
+        let cpi_ctx = CpiContext::new_with_signer(
+            accounts.token_program.to_account_info(),
+            token_interface::TransferChecked {
+                from: accounts.stake_token_vault.to_account_info(),
+                mint: accounts.stake_token_mint.to_account_info(),
+                to: penalty_to,
+                authority: accounts.stake_token_vault.to_account_info(),
+            },
+            signer,
+        );
+        token_interface::transfer_checked(cpi_ctx, penalty, accounts.stake_token_mint.decimals)?;
+        accounts.staking.total_staked = accounts.staking.total_staked.safe_sub(penalty)?;
+    }
+
+    //a transfer fee mint withholds part of the parked amount
+    accounts.unbonding_vault.reload()?;
+    let parked = accounts.unbonding_vault.amount.safe_sub(unbonding_before)?;
+
+    accounts.stake_token_vault.reload()?;
+    accounts.x_token_mint.reload()?;
+    accounts
+        .staking
+        .check_backing(accounts.stake_token_vault.amount)?;
+
+    //record the pending withdrawal
+    let maturity = (now_ts as u64).safe_add(accounts.staking.cooldown_duration)?;
+    let unstake_request = &mut accounts.unstake_request;
+    unstake_request.user = accounts.user.key();
+    unstake_request.staking = accounts.staking.key();
+    unstake_request.amount = parked;
+    unstake_request.maturity = maturity;
+
+    let user_staking = &mut accounts.user_staking;
+    user_staking.unstake_request_count = user_staking.unstake_request_count.safe_add(1)?;
+    user_staking.open_unstake_requests = user_staking.open_unstake_requests.safe_add(1)?;
+
+    let new_price = get_price(&accounts.staking, &accounts.x_token_mint);
+
+    emit!(UnstakeRequested {
+        user: accounts.user.key(),
+        request: accounts.unstake_request.key(),
+        x_token_amount: amount,
+        amount: parked,
+        maturity,
+    });
+
+    emit!(PriceChange {
+        old_step_per_xstep_e18: old_price.scaled(Rounding::Down)?,
+        new_step_per_xstep_e18: new_price.scaled(Rounding::Down)?,
+    });
+
+    Ok((parked, penalty))
+}
+
+#[derive(Accounts)]
+pub struct Withdraw<'info> {
+    pub stake_token_mint: Box<InterfaceAccount<'info, token_interface::Mint>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// the vault is the authority of the unbonding vault
+    #[account(
+        seeds = [ stake_token_mint.key().as_ref() ],
+        bump,
+    )]
+    pub stake_token_vault: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    #[account(
+        seeds = [ crate::constants::STAKING_PDA_SEED, stake_token_mint.key().as_ref() ],
+        bump,
+        constraint = !staking.is_paused(PAUSE_UNSTAKE) @ ErrorCode::Paused,
+    )]
+    pub staking: Account<'info, StakingAccount>,
+
+    #[account(
+        mut,
+        seeds = [ crate::constants::UNBONDING_VAULT_SEED, stake_token_mint.key().as_ref() ],
+        bump,
+    )]
+    pub unbonding_vault: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [ staking.key().as_ref(), user.key().as_ref() ],
+        bump,
+    )]
+    pub user_staking: Account<'info, UserStakingAccount>,
+
+    #[account(
+        mut,
+        close = user,
+        has_one = user,
+        has_one = staking,
+    )]
+    pub unstake_request: Account<'info, UnstakeRequest>,
+
+    #[account(
+        mut,
+        token::mint = stake_token_mint,
+        token::authority = user,
+    )]
+    //the token account to send token
+    pub token_to: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    pub token_program: Interface<'info, token_interface::TokenInterface>,
+}
+
+pub fn withdraw(ctx: Context<Withdraw>) -> Result<()> {
+    let now_ts = Clock::get()?.unix_timestamp;
+    let maturity = ctx.accounts.unstake_request.maturity;
+
+    //emergency mode releases pending requests right away
+    if !ctx.accounts.staking.emergency_mode && (now_ts as u64) < maturity {
+        msg!("Cooling down for {} more seconds", maturity - now_ts as u64);
+        return Err(ErrorCode::CooldownNotElapsed.into());
+    }
+
+    //compute vault signer seeds
+    let stake_token_mint_key = ctx.accounts.stake_token_mint.key();
+    let seeds = &[
+        stake_token_mint_key.as_ref(),
+        &[ctx.bumps.stake_token_vault],
+    ];
+    let signer = &[&seeds[..]];
+
+    //transfer from unbonding vault to user
+    let amount = ctx.accounts.unstake_request.amount;
+    let cpi_ctx = CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        token_interface::TransferChecked {
+            from: ctx.accounts.unbonding_vault.to_account_info(),
+            mint: ctx.accounts.stake_token_mint.to_account_info(),
+            to: ctx.accounts.token_to.to_account_info(),
+            authority: ctx.accounts.stake_token_vault.to_account_info(),
+        },
+        signer,
+    );
+    token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.stake_token_mint.decimals)?;
+
+    let user_staking = &mut ctx.accounts.user_staking;
+    user_staking.open_unstake_requests = user_staking.open_unstake_requests.safe_sub(1)?;
+
+    emit!(Withdrawn {
+        user: ctx.accounts.user.key(),
+        request: ctx.accounts.unstake_request.key(),
+        amount,
+    });
+
+    Ok(())
+}
@@ -1,135 +1,284 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{Mint, Token, TokenAccount};
+use anchor_spl::token_interface;
 
+use crate::instructions::{accrue_rewards, get_price, BPS_DENOMINATOR};
+use crate::math::{mul_div, Rounding, SafeMath};
+use crate::{ErrorCode, PriceChange, StakingAccount, UnstakedTo, PAUSE_UNSTAKE};
+
+/// the accounts every unstake path burns x tokens and pays out from
 #[derive(Accounts)]
-#[instruction(nonce_vault: u8, _nonce_staking: u8, _nonce_user_staking: u8, amount: u64)]
-pub struct Unstake<'info> {
+pub struct Redeem<'info> {
+    pub stake_token_mint: Box<InterfaceAccount<'info, token_interface::Mint>>,
+
+    //the authority allowed to burn from user_x_token_account
+    pub user: Signer<'info>,
+
     #[account(
-        address = constants::STEP_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap(),
+        mut,
+        seeds = [ stake_token_mint.key().as_ref() ],
+        bump,
     )]
-    pub token_mint: Box<Account<'info, Mint>>,
+    pub stake_token_vault: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
 
-    //the authority allowed to transfer from x_token_from
-    pub x_token_from_authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [ crate::constants::STAKING_PDA_SEED, stake_token_mint.key().as_ref() ],
+        bump,
+        constraint = !staking.is_paused(PAUSE_UNSTAKE) @ ErrorCode::Paused,
+        constraint = !staking.emergency_mode @ ErrorCode::EmergencyMode,
+    )]
+    pub staking: Account<'info, StakingAccount>,
 
+    /// the reserve rewards are dripped from
     #[account(
         mut,
-        seeds = [ token_mint.key().as_ref() ],
-        bump = nonce_vault,
+        seeds = [ crate::constants::REWARD_RESERVE_SEED, stake_token_mint.key().as_ref() ],
+        bump,
     )]
-    pub token_vault: Box<Account<'info, TokenAccount>>,
+    pub reward_reserve: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
 
     #[account(
         mut,
-        seeds = [ constants::STAKING_PDA_SEED.as_ref() ],
-        bump = _nonce_staking,
-        constraint = !staking_account.freeze_program,
+        address = staking.x_token_mint,
     )]
-    pub staking_account: ProgramAccount<'info, StakingAccount>,
+    pub x_token_mint: Box<InterfaceAccount<'info, token_interface::Mint>>,
 
+    /// the x token account to burn from
     #[account(
         mut,
-        seeds = [ x_token_from_authority.key().as_ref() ],
-        bump = _nonce_user_staking,
-        constraint = user_staking_account.x_token_amount >= amount
+        token::mint = x_token_mint,
+        token::authority = user,
+        token::token_program = x_token_program,
     )]
-    pub user_staking_account: ProgramAccount<'info, UserStakingAccount>,
+    pub user_x_token_account: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
 
-    #[account(mut)]
-    //the token account to send token
-    pub token_to: Box<Account<'info, TokenAccount>>,
+    pub token_program: Interface<'info, token_interface::TokenInterface>,
+    pub x_token_program: Interface<'info, token_interface::TokenInterface>,
+}
 
-    pub token_program: Program<'info, Token>,
+#[derive(Accounts)]
+pub struct Unstake<'info> {
+    pub redeem: Redeem<'info>,
+
+    /// the user's own token account, unstake_to pays elsewhere
+    #[account(
+        mut,
+        token::mint = redeem.stake_token_mint,
+        token::authority = redeem.user,
+    )]
+    pub token_to: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
 }
 
-pub fn unstake(
+/// unstake paying out to a token account the user does not have to own
+#[derive(Accounts)]
+pub struct UnstakeTo<'info> {
+    pub redeem: Redeem<'info>,
+
+    #[account(
+        mut,
+        token::mint = redeem.stake_token_mint,
+    )]
+    pub recipient: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+}
+
+/// how many x tokens to unstake, each with its own slippage bound
+#[derive(Clone, Copy)]
+pub enum UnstakeAmount {
+    /// burn exactly `shares` x tokens, paying at least `min_tokens_out`
+    Shares { shares: u64, min_tokens_out: u64 },
+    /// pay exactly `tokens` before any penalty, burning at most `max_shares_in`
+    Tokens { tokens: u64, max_shares_in: u64 },
+}
+
+pub fn unstake(ctx: Context<Unstake>, amount: u64, min_tokens_out: u64) -> Result<()> {
+    require!(amount > 0, ErrorCode::ZeroAmount);
+
+    require_no_cooldown(&ctx.accounts.redeem)?;
+
+    let token_to = ctx.accounts.token_to.to_account_info();
+    process_unstake(
+        &mut ctx.accounts.redeem,
+        &ctx.bumps.redeem,
+        UnstakeAmount::Shares {
+            shares: amount,
+            min_tokens_out,
+        },
+        token_to,
+        0,
+        None,
+    )?;
+
+    Ok(())
+}
+
+pub fn unstake_exact_tokens(
     ctx: Context<Unstake>,
-    nonce_vault: u8,
-    _nonce_staking: u8,
-    _nonce_user_staking: u8,
-    amount: u64,
-) -> ProgramResult {
-    let now_ts = Clock::get().unwrap().unix_timestamp;
-    let lock_end_date = ctx.accounts.staking_account.lock_end_date;
-
-    if (now_ts as u64) < lock_end_date {
-        return Err(ErrorCode::NotExceedLockEndDate.into());
-    }
+    token_amount: u64,
+    max_shares_in: u64,
+) -> Result<()> {
+    require!(token_amount > 0, ErrorCode::ZeroAmount);
 
-    let total_token = ctx.accounts.token_vault.amount;
-    let total_x_token = ctx.accounts.staking_account.total_x_token;
-    let old_price = get_price(&ctx.accounts.token_vault, &ctx.accounts.staking_account);
+    require_no_cooldown(&ctx.accounts.redeem)?;
+
+    let token_to = ctx.accounts.token_to.to_account_info();
+    process_unstake(
+        &mut ctx.accounts.redeem,
+        &ctx.bumps.redeem,
+        UnstakeAmount::Tokens {
+            tokens: token_amount,
+            max_shares_in,
+        },
+        token_to,
+        0,
+        None,
+    )?;
+
+    Ok(())
+}
+
+pub fn unstake_to(ctx: Context<UnstakeTo>, amount: u64, min_tokens_out: u64) -> Result<()> {
+    require!(amount > 0, ErrorCode::ZeroAmount);
+
+    require_no_cooldown(&ctx.accounts.redeem)?;
+
+    let recipient = ctx.accounts.recipient.to_account_info();
+    let (_, paid, _) = process_unstake(
+        &mut ctx.accounts.redeem,
+        &ctx.bumps.redeem,
+        UnstakeAmount::Shares {
+            shares: amount,
+            min_tokens_out,
+        },
+        recipient,
+        0,
+        None,
+    )?;
+
+    emit!(UnstakedTo {
+        user: ctx.accounts.redeem.user.key(),
+        recipient: ctx.accounts.recipient.key(),
+        x_token_amount: amount,
+        amount: paid,
+    });
+
+    Ok(())
+}
+
+fn require_no_cooldown(accounts: &Redeem) -> Result<()> {
+    require!(
+        accounts.staking.cooldown_duration == 0,
+        ErrorCode::CooldownRequired
+    );
+
+    Ok(())
+}
+
+/// burns x tokens and pays their vault share to `token_to`, minus a
+/// `penalty_bps` cut that goes to `penalty_to` or stays in the vault.
+/// rounding always favors the vault: paid tokens round down and burned
+/// shares round up. returns the burned shares, the paid amount and the penalty
+pub fn process_unstake<'info>(
+    accounts: &mut Redeem<'info>,
+    bumps: &RedeemBumps,
+    amount: UnstakeAmount,
+    token_to: AccountInfo<'info>,
+    penalty_bps: u64,
+    penalty_to: Option<AccountInfo<'info>>,
+) -> Result<(u64, u64, u64)> {
+    accrue_rewards(
+        &mut accounts.staking,
+        &accounts.stake_token_mint,
+        &mut accounts.reward_reserve,
+        &mut accounts.stake_token_vault,
+        &accounts.token_program,
+        bumps.stake_token_vault,
+    )?;
+
+    let old_price = get_price(&accounts.staking, &accounts.x_token_mint);
+
+    //determine the shares to burn and the user share of vault
+    let (shares, what) = match amount {
+        UnstakeAmount::Shares { shares, .. } => {
+            (shares, old_price.to_tokens(shares, Rounding::Down)?)
+        }
+        UnstakeAmount::Tokens {
+            tokens,
+            max_shares_in,
+        } => {
+            let shares = old_price.to_shares(tokens, Rounding::Up)?;
+            require!(shares <= max_shares_in, ErrorCode::SlippageExceeded);
+            (shares, tokens)
+        }
+    };
+    require!(
+        accounts.user_x_token_account.amount >= shares,
+        ErrorCode::InsufficientShares
+    );
 
     //burn what is being sent
-    ctx.accounts.staking_account.total_x_token = (ctx.accounts.staking_account.total_x_token
-        as u128)
-        .checked_sub(amount as u128)
-        .unwrap()
-        .try_into()
-        .unwrap();
-    ctx.accounts.user_staking_account.x_token_amount =
-        (ctx.accounts.user_staking_account.x_token_amount as u128)
-            .checked_sub(amount as u128)
-            .unwrap()
-            .try_into()
-            .unwrap();
-
-    //determine user share of vault
-    let what: u64 = (amount as u128)
-        .checked_mul(total_token as u128)
-        .unwrap()
-        .checked_div(total_x_token as u128)
-        .unwrap()
-        .try_into()
-        .unwrap();
+    let cpi_ctx = CpiContext::new(
+        accounts.x_token_program.to_account_info(),
+        token_interface::Burn {
+            mint: accounts.x_token_mint.to_account_info(),
+            from: accounts.user_x_token_account.to_account_info(),
+            authority: accounts.user.to_account_info(),
+        },
+    );
+    token_interface::burn(cpi_ctx, shares)?;
+
+    let penalty = mul_div(what, penalty_bps, BPS_DENOMINATOR)?;
+    let paid = what.safe_sub(penalty)?;
+    if let UnstakeAmount::Shares { min_tokens_out, .. } = amount {
+        require!(paid >= min_tokens_out, ErrorCode::SlippageExceeded);
+    }
 
     //compute vault signer seeds
-    let token_mint_key = ctx.accounts.token_mint.key();
-    let seeds = &[token_mint_key.as_ref(), &[nonce_vault]];
+    let stake_token_mint_key = accounts.stake_token_mint.key();
+    let seeds = &[stake_token_mint_key.as_ref(), &[bumps.stake_token_vault]];
     let signer = &[&seeds[..]];
 
     //transfer from vault to user
     let cpi_ctx = CpiContext::new_with_signer(
-        ctx.accounts.token_program.to_account_info(),
-        token::Transfer {
-            from: ctx.accounts.token_vault.to_account_info(),
-            to: ctx.accounts.token_to.to_account_info(),
-            authority: ctx.accounts.token_vault.to_account_info(),
+        accounts.token_program.to_account_info(),
+        token_interface::TransferChecked {
+            from: accounts.stake_token_vault.to_account_info(),
+            mint: accounts.stake_token_mint.to_account_info(),
+            to: token_to,
+            authority: accounts.stake_token_vault.to_account_info(),
         },
         signer,
     );
-    token::transfer(cpi_ctx, what)?;
-
-    (&mut ctx.accounts.token_vault).reload()?;
-
-    //determine user staking amount
-    let new_total_token = ctx.accounts.token_vault.amount;
-    let new_total_x_token = ctx.accounts.staking_account.total_x_token;
-
-    if new_total_token == 0 || new_total_x_token == 0 {
-        ctx.accounts.user_staking_account.amount = 0;
-    } else {
-        let new_what: u64 = (ctx.accounts.user_staking_account.x_token_amount as u128)
-            .checked_mul(new_total_token as u128)
-            .unwrap()
-            .checked_div(new_total_x_token as u128)
-            .unwrap()
-            .try_into()
-            .unwrap();
-
-        if new_what < ctx.accounts.user_staking_account.amount {
-            ctx.accounts.user_staking_account.amount = new_what;
-        }
+    token_interface::transfer_checked(cpi_ctx, paid, accounts.stake_token_mint.decimals)?;
+    accounts.staking.total_staked = accounts.staking.total_staked.safe_sub(paid)?;
+
+    //a penalty without a receiver stays in the vault for the remaining stakers
+    if let Some(penalty_to) = penalty_to.filter(|_| penalty > 0) {
+        let cpi_ctx = CpiContext::new_with_signer(
+            accounts.token_program.to_account_info(),
+            token_interface::TransferChecked {
+                from: accounts.stake_token_vault.to_account_info(),
+                mint: accounts.stake_token_mint.to_account_info(),
+                to: penalty_to,
+                authority: accounts.stake_token_vault.to_account_info(),
+            },
+            signer,
+        );
+        token_interface::transfer_checked(cpi_ctx, penalty, accounts.stake_token_mint.decimals)?;
+        accounts.staking.total_staked = accounts.staking.total_staked.safe_sub(penalty)?;
     }
 
-    let new_price = get_price(&ctx.accounts.token_vault, &ctx.accounts.staking_account);
+    accounts.stake_token_vault.reload()?;
+    accounts.x_token_mint.reload()?;
+    accounts
+        .staking
+        .check_backing(accounts.stake_token_vault.amount)?;
+
+    let new_price = get_price(&accounts.staking, &accounts.x_token_mint);
 
     emit!(PriceChange {
-        old_step_per_xstep_e9: old_price.0,
-        old_step_per_xstep: old_price.1,
-        new_step_per_xstep_e9: new_price.0,
-        new_step_per_xstep: new_price.1,
+        old_step_per_xstep_e18: old_price.scaled(Rounding::Down)?,
+        new_step_per_xstep_e18: new_price.scaled(Rounding::Down)?,
     });
 
-    Ok(())
+    Ok((shares, paid, penalty))
 }
@@ -0,0 +1,225 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface;
+
+use crate::instructions::unbonding::*;
+use crate::instructions::unstake::*;
+use crate::instructions::{accrue_rewards, compound_emission, transfer_from_escrow};
+use crate::math::mul_div;
+use crate::{EarlyUnstake, ErrorCode, StakingAccount, UserStakingAccount};
+
+#[derive(Accounts)]
+pub struct UnstakeEarly<'info> {
+    pub unstake: Unstake<'info>,
+
+    #[account(
+        mut,
+        seeds = [ unstake.redeem.staking.key().as_ref(), unstake.redeem.user.key().as_ref() ],
+        bump,
+    )]
+    pub user_staking: Account<'info, UserStakingAccount>,
+
+    /// the locked shares to unstake are taken from here
+    #[account(
+        mut,
+        seeds = [ crate::constants::POSITION_ESCROW_SEED, user_staking.key().as_ref() ],
+        bump,
+    )]
+    pub position_escrow: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    /// stake fee account, owned by the fee receiver
+    #[account(
+        mut,
+        constraint = stake_fee.mint == unstake.redeem.stake_token_mint.key(),
+        token::authority = crate::constants::STAKE_FEE_RECEIVER.parse::<Pubkey>().unwrap(),
+    )]
+    pub stake_fee: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+}
+
+pub fn unstake_early(ctx: Context<UnstakeEarly>, amount: u64, min_tokens_out: u64) -> Result<()> {
+    require!(amount > 0, ErrorCode::ZeroAmount);
+
+    let staking = &ctx.accounts.unstake.redeem.staking;
+    require!(staking.cooldown_duration == 0, ErrorCode::CooldownRequired);
+
+    let now_ts = Clock::get()?.unix_timestamp as u64;
+    let config = staking.early_unstake_penalty;
+    let penalty_bps = early_unstake_penalty_bps(staking, &ctx.accounts.user_staking, now_ts)?;
+
+    let redeem = &mut ctx.accounts.unstake.redeem;
+    accrue_rewards(
+        &mut redeem.staking,
+        &redeem.stake_token_mint,
+        &mut redeem.reward_reserve,
+        &mut redeem.stake_token_vault,
+        &redeem.token_program,
+        ctx.bumps.unstake.redeem.stake_token_vault,
+    )?;
+    compound_emission(
+        &mut redeem.staking,
+        &mut ctx.accounts.user_staking,
+        &mut redeem.x_token_mint,
+        &mut ctx.accounts.position_escrow,
+        &redeem.stake_token_vault,
+        &redeem.x_token_program,
+        ctx.bumps.unstake.redeem.stake_token_vault,
+    )?;
+
+    //move the locked shares into the wallet they are burned from
+    let escrowed = ctx.accounts.position_escrow.amount;
+    require!(escrowed >= amount, ErrorCode::InsufficientShares);
+    ctx.accounts.user_staking.remove_shares(
+        &mut ctx.accounts.unstake.redeem.staking,
+        amount,
+        escrowed,
+        now_ts,
+    )?;
+    transfer_from_escrow(
+        &ctx.accounts.position_escrow,
+        ctx.accounts
+            .unstake
+            .redeem
+            .user_x_token_account
+            .to_account_info(),
+        &ctx.accounts.unstake.redeem.x_token_mint,
+        &ctx.accounts.unstake.redeem.stake_token_vault,
+        &ctx.accounts.unstake.redeem.x_token_program,
+        ctx.bumps.unstake.redeem.stake_token_vault,
+        amount,
+    )?;
+    ctx.accounts.unstake.redeem.user_x_token_account.reload()?;
+
+    let penalty_to = config
+        .to_fee_receiver
+        .then(|| ctx.accounts.stake_fee.to_account_info());
+
+    let token_to = ctx.accounts.unstake.token_to.to_account_info();
+    let (_, paid, penalty) = process_unstake(
+        &mut ctx.accounts.unstake.redeem,
+        &ctx.bumps.unstake.redeem,
+        UnstakeAmount::Shares {
+            shares: amount,
+            min_tokens_out,
+        },
+        token_to,
+        penalty_bps,
+        penalty_to,
+    )?;
+
+    emit!(EarlyUnstake {
+        user: ctx.accounts.unstake.redeem.user.key(),
+        x_token_amount: amount,
+        amount: paid,
+        penalty,
+    });
+
+    Ok(())
+}
+
+/// the early exit of pools with a cooldown, the penalty is cut when the
+/// locked shares are moved into an unstake request
+#[derive(Accounts)]
+pub struct RequestUnstakeEarly<'info> {
+    pub request: RequestUnstake<'info>,
+
+    /// stake fee account, owned by the fee receiver
+    #[account(
+        mut,
+        constraint = stake_fee.mint == request.stake_token_mint.key(),
+        token::authority = crate::constants::STAKE_FEE_RECEIVER.parse::<Pubkey>().unwrap(),
+    )]
+    pub stake_fee: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
+}
+
+pub fn request_unstake_early(
+    ctx: Context<RequestUnstakeEarly>,
+    amount: u64,
+    min_tokens_out: u64,
+) -> Result<()> {
+    require!(amount > 0, ErrorCode::ZeroAmount);
+
+    let now_ts = Clock::get()?.unix_timestamp as u64;
+    let request = &mut ctx.accounts.request;
+    let config = request.staking.early_unstake_penalty;
+    let penalty_bps = early_unstake_penalty_bps(&request.staking, &request.user_staking, now_ts)?;
+
+    accrue_rewards(
+        &mut request.staking,
+        &request.stake_token_mint,
+        &mut request.reward_reserve,
+        &mut request.stake_token_vault,
+        &request.token_program,
+        ctx.bumps.request.stake_token_vault,
+    )?;
+    compound_emission(
+        &mut request.staking,
+        &mut request.user_staking,
+        &mut request.x_token_mint,
+        &mut request.position_escrow,
+        &request.stake_token_vault,
+        &request.x_token_program,
+        ctx.bumps.request.stake_token_vault,
+    )?;
+
+    //move the locked shares into the wallet they are burned from
+    let escrowed = request.position_escrow.amount;
+    require!(escrowed >= amount, ErrorCode::InsufficientShares);
+    request
+        .user_staking
+        .remove_shares(&mut request.staking, amount, escrowed, now_ts)?;
+    transfer_from_escrow(
+        &request.position_escrow,
+        request.user_x_token_account.to_account_info(),
+        &request.x_token_mint,
+        &request.stake_token_vault,
+        &request.x_token_program,
+        ctx.bumps.request.stake_token_vault,
+        amount,
+    )?;
+    request.user_x_token_account.reload()?;
+
+    let penalty_to = config
+        .to_fee_receiver
+        .then(|| ctx.accounts.stake_fee.to_account_info());
+
+    let (parked, penalty) = process_request_unstake(
+        &mut ctx.accounts.request,
+        &ctx.bumps.request,
+        amount,
+        min_tokens_out,
+        penalty_bps,
+        penalty_to,
+    )?;
+
+    emit!(EarlyUnstake {
+        user: ctx.accounts.request.user.key(),
+        x_token_amount: amount,
+        amount: parked,
+        penalty,
+    });
+
+    Ok(())
+}
+
+/// the penalty in basis points for unstaking the position now, none once its lock has ended
+fn early_unstake_penalty_bps(
+    staking: &StakingAccount,
+    user_staking: &UserStakingAccount,
+    now_ts: u64,
+) -> Result<u64> {
+    let config = staking.early_unstake_penalty;
+
+    let remaining = user_staking.lock_end_date.saturating_sub(now_ts);
+    if remaining == 0 {
+        return Ok(0);
+    }
+    if !config.decay {
+        return Ok(config.penalty_bps as u64);
+    }
+
+    //scale down linearly over the lock of the chosen tier
+    let duration = staking.lock_tiers[user_staking.lock_tier as usize].duration;
+    if duration == 0 {
+        return Ok(0);
+    }
+    mul_div(config.penalty_bps as u64, remaining.min(duration), duration)
+}
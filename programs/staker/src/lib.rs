@@ -1,8 +1,11 @@
 pub mod instructions;
+pub mod math;
 
 use anchor_lang::prelude::*;
+use anchor_spl::token_interface;
 
 use instructions::*;
+use math::{mul_div_up, to_u64, ExchangeRate, Rounding, SafeMath};
 
 #[cfg(feature = "devnet")]
 declare_id!("J9z9v5ZMEZoxHNhVHw2QkJvWCjYTqQhCL1KFmYt3wKVC");
@@ -11,353 +14,919 @@ declare_id!("J9z9v5ZMEZoxHNhVHw2QkJvWCjYTqQhCL1KFmYt3wKVC");
 
 #[cfg(feature = "devnet")]
 pub mod constants {
-    pub const PLENTY_TOKEN_MINT_PUBKEY: &str = "Eat2Gpa1jKRBqXNjZsSo4cNLxnBfoeKAMuH2BYmVintR";
     pub const STAKE_FEE_RECEIVER: &str = "AMyJM7SrZCQxFTNPGwPuyCHgSFa6u7WhDrJPXL3jyXyB";
     pub const STAKING_PDA_SEED: &[u8] = b"staking";
+    pub const X_TOKEN_MINT_SEED: &[u8] = b"x_token";
+    pub const REWARD_RESERVE_SEED: &[u8] = b"reward_reserve";
+    pub const REWARD_VAULT_SEED: &[u8] = b"reward_vault";
+    pub const UNBONDING_VAULT_SEED: &[u8] = b"unbonding_vault";
+    pub const PENDING_CHANGE_SEED: &[u8] = b"pending_change";
+    pub const POSITION_ESCROW_SEED: &[u8] = b"position_escrow";
+    pub const UNSTAKE_REQUEST_SEED: &[u8] = b"unstake_request";
 }
 
 #[cfg(not(feature = "devnet"))]
 pub mod constants {
-    pub const PLENTY_TOKEN_MINT_PUBKEY: &str = "Eat2Gpa1jKRBqXNjZsSo4cNLxnBfoeKAMuH2BYmVintR";
     pub const STAKE_FEE_RECEIVER: &str = "AMyJM7SrZCQxFTNPGwPuyCHgSFa6u7WhDrJPXL3jyXyB";
     pub const STAKING_PDA_SEED: &[u8] = b"staking";
+    pub const X_TOKEN_MINT_SEED: &[u8] = b"x_token";
+    pub const REWARD_RESERVE_SEED: &[u8] = b"reward_reserve";
+    pub const REWARD_VAULT_SEED: &[u8] = b"reward_vault";
+    pub const UNBONDING_VAULT_SEED: &[u8] = b"unbonding_vault";
+    pub const PENDING_CHANGE_SEED: &[u8] = b"pending_change";
+    pub const POSITION_ESCROW_SEED: &[u8] = b"position_escrow";
+    pub const UNSTAKE_REQUEST_SEED: &[u8] = b"unstake_request";
 }
 
 #[program]
 pub mod staker {
     use super::*;
 
-    // pub fn initialize(
-    //     ctx: Context<Initialize>,
-    //     _nonce_vault: u8,
-    //     _nonce_staking: u8,
-    //     lock_end_date: u64,
-    // ) -> ProgramResult {
-    //     msg!("Greetings from: {:?}", ctx.program_id);
-    //     ctx.accounts.staking_account.initializer_key = *ctx.accounts.initializer.key;
-    //     ctx.accounts.staking_account.lock_end_date = lock_end_date;
+    pub fn initialize(
+        ctx: Context<Initialize>,
+        lock_tiers: [LockTier; MAX_LOCK_TIERS],
+    ) -> Result<()> {
+        instructions::initialize(ctx, lock_tiers)
+    }
+
+    pub fn stake(
+        ctx: Context<Stake>,
+        stake_amount: u64,
+        lock_tier: u8,
+        min_shares_out: u64,
+    ) -> Result<()> {
+        instructions::stake(ctx, stake_amount, lock_tier, min_shares_out)
+    }
+
+    pub fn stake_for(
+        ctx: Context<StakeFor>,
+        stake_amount: u64,
+        lock_tier: u8,
+        min_shares_out: u64,
+    ) -> Result<()> {
+        instructions::stake_for(ctx, stake_amount, lock_tier, min_shares_out)
+    }
+
+    pub fn queue_change(ctx: Context<QueueChange>, change: ParameterChange) -> Result<()> {
+        instructions::queue_change(ctx, change)
+    }
+
+    pub fn execute_change(ctx: Context<ExecuteChange>) -> Result<()> {
+        instructions::execute_change(ctx)
+    }
 
-    //     Ok(())
-    // }
+    pub fn cancel_change(ctx: Context<CancelChange>) -> Result<()> {
+        instructions::cancel_change(ctx)
+    }
 
-    pub fn stake(ctx: Context<Stake>, stake_amount: u64) -> Result<()> {
-        instructions::stake(ctx, stake_amount)
+    pub fn fund_rewards(ctx: Context<FundRewards>, amount: u64) -> Result<()> {
+        instructions::fund_rewards(ctx, amount)
     }
 
-    pub fn update_lock_end_date(
-        ctx: Context<UpdateLockEndDate>,
-        _nonce_staking: u8,
-        new_lock_end_date: u64,
-    ) -> ProgramResult {
-        ctx.accounts.staking_account.lock_end_date = new_lock_end_date;
+    pub fn crank(ctx: Context<Crank>) -> Result<()> {
+        instructions::crank(ctx)
+    }
+
+    pub fn add_reward(ctx: Context<AddReward>) -> Result<()> {
+        instructions::add_reward(ctx)
+    }
+
+    pub fn claim_rewards<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClaimRewards<'info>>,
+    ) -> Result<()> {
+        instructions::claim_rewards(ctx)
+    }
+
+    pub fn propose_admin(ctx: Context<ProposeAdmin>, new_admin: Pubkey) -> Result<()> {
+        instructions::propose_admin(ctx, new_admin)
+    }
+
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        instructions::accept_admin(ctx)
+    }
+
+    pub fn set_roles(
+        ctx: Context<SetRoles>,
+        pauser: Pubkey,
+        fee_manager: Pubkey,
+        parameter_manager: Pubkey,
+    ) -> Result<()> {
+        instructions::set_roles(ctx, pauser, fee_manager, parameter_manager)
+    }
+
+    pub fn set_emergency_mode(ctx: Context<SetEmergencyMode>, enabled: bool) -> Result<()> {
+        ctx.accounts.staking.emergency_mode = enabled;
+
+        emit!(EmergencyModeChanged { enabled });
 
         Ok(())
     }
 
-    pub fn toggle_freeze_program(ctx: Context<FreezeProgram>, _nonce_staking: u8) -> ProgramResult {
-        ctx.accounts.staking_account.freeze_program = !ctx.accounts.staking_account.freeze_program;
+    pub fn emergency_withdraw(ctx: Context<EmergencyWithdraw>) -> Result<()> {
+        instructions::emergency_withdraw(ctx)
+    }
+
+    pub fn emergency_unstake(ctx: Context<EmergencyUnstake>, amount: u64) -> Result<()> {
+        instructions::emergency_unstake(ctx, amount)
+    }
+
+    pub fn set_pause(ctx: Context<SetPause>, flags: u8) -> Result<()> {
+        require!(flags & !PAUSE_ALL == 0, ErrorCode::InvalidPauseFlags);
+
+        let old_flags = ctx.accounts.staking.pause_flags;
+        ctx.accounts.staking.pause_flags = flags;
+
+        emit!(PauseChanged {
+            old_flags,
+            new_flags: flags,
+        });
 
         Ok(())
     }
 
-    pub fn unstake(ctx: Context<Stake>, unstake_amount: u64) -> Result<()> {
-        instructions::unstake(ctx, unstake_amount)
+    pub fn release(ctx: Context<Release>, amount: u64) -> Result<()> {
+        instructions::release(ctx, amount)
+    }
+
+    pub fn unstake(ctx: Context<Unstake>, unstake_amount: u64, min_tokens_out: u64) -> Result<()> {
+        instructions::unstake(ctx, unstake_amount, min_tokens_out)
+    }
+
+    pub fn unstake_exact_tokens(
+        ctx: Context<Unstake>,
+        token_amount: u64,
+        max_shares_in: u64,
+    ) -> Result<()> {
+        instructions::unstake_exact_tokens(ctx, token_amount, max_shares_in)
+    }
+
+    pub fn unstake_to(
+        ctx: Context<UnstakeTo>,
+        unstake_amount: u64,
+        min_tokens_out: u64,
+    ) -> Result<()> {
+        instructions::unstake_to(ctx, unstake_amount, min_tokens_out)
+    }
+
+    pub fn unstake_early(
+        ctx: Context<UnstakeEarly>,
+        unstake_amount: u64,
+        min_tokens_out: u64,
+    ) -> Result<()> {
+        instructions::unstake_early(ctx, unstake_amount, min_tokens_out)
     }
 
-    pub fn unstake_admin(
-        ctx: Context<UnstakeAdmin>,
-        nonce_vault: u8,
-        _nonce_staking: u8,
+    pub fn request_unstake(
+        ctx: Context<RequestUnstake>,
         amount: u64,
-    ) -> ProgramResult {
-        let total_token = ctx.accounts.token_vault.amount;
-        let total_x_token = ctx.accounts.staking_account.total_x_token;
-        let old_price = get_price(&ctx.accounts.token_vault, &ctx.accounts.staking_account);
-
-        //burn what is being sent
-        ctx.accounts.staking_account.total_x_token = (ctx.accounts.staking_account.total_x_token
-            as u128)
-            .checked_sub(amount as u128)
-            .unwrap()
-            .try_into()
-            .unwrap();
-        ctx.accounts.user_staking_account.x_token_amount =
-            (ctx.accounts.user_staking_account.x_token_amount as u128)
-                .checked_sub(amount as u128)
-                .unwrap()
-                .try_into()
-                .unwrap();
-
-        //determine user share of vault
-        let what: u64 = (amount as u128)
-            .checked_mul(total_token as u128)
-            .unwrap()
-            .checked_div(total_x_token as u128)
-            .unwrap()
-            .try_into()
-            .unwrap();
-
-        //compute vault signer seeds
-        let token_mint_key = ctx.accounts.token_mint.key();
-        let seeds = &[token_mint_key.as_ref(), &[nonce_vault]];
-        let signer = &[&seeds[..]];
-
-        //transfer from vault to user
-        let cpi_ctx = CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
-            token::Transfer {
-                from: ctx.accounts.token_vault.to_account_info(),
-                to: ctx.accounts.token_to.to_account_info(),
-                authority: ctx.accounts.token_vault.to_account_info(),
-            },
-            signer,
-        );
-        token::transfer(cpi_ctx, what)?;
+        min_tokens_out: u64,
+    ) -> Result<()> {
+        instructions::request_unstake(ctx, amount, min_tokens_out)
+    }
 
-        (&mut ctx.accounts.token_vault).reload()?;
+    pub fn request_unstake_early(
+        ctx: Context<RequestUnstakeEarly>,
+        amount: u64,
+        min_tokens_out: u64,
+    ) -> Result<()> {
+        instructions::request_unstake_early(ctx, amount, min_tokens_out)
+    }
 
-        //determine user staking amount
-        let new_total_token = ctx.accounts.token_vault.amount;
-        let new_total_x_token = ctx.accounts.staking_account.total_x_token;
+    pub fn withdraw(ctx: Context<Withdraw>) -> Result<()> {
+        instructions::withdraw(ctx)
+    }
 
-        if new_total_token == 0 || new_total_x_token == 0 {
-            ctx.accounts.user_staking_account.amount = 0;
-        } else {
-            let new_what: u64 = (ctx.accounts.user_staking_account.x_token_amount as u128)
-                .checked_mul(new_total_token as u128)
-                .unwrap()
-                .checked_div(new_total_x_token as u128)
-                .unwrap()
-                .try_into()
-                .unwrap();
-
-            if new_what < ctx.accounts.user_staking_account.amount {
-                ctx.accounts.user_staking_account.amount = new_what;
-            }
-        }
+    pub fn force_exit(ctx: Context<ForceExit>, amount: u64, reason: u8) -> Result<()> {
+        instructions::force_exit(ctx, amount, reason)
+    }
 
-        let new_price = get_price(&ctx.accounts.token_vault, &ctx.accounts.staking_account);
+    pub fn close_position(ctx: Context<ClosePosition>) -> Result<()> {
+        instructions::close_position(ctx)
+    }
 
-        emit!(PriceChange {
-            old_step_per_xstep_e9: old_price.0,
-            old_step_per_xstep: old_price.1,
-            new_step_per_xstep_e9: new_price.0,
-            new_step_per_xstep: new_price.1,
-        });
+    pub fn sweep_position(ctx: Context<SweepPosition>) -> Result<()> {
+        instructions::sweep_position(ctx)
+    }
 
-        Ok(())
+    pub fn sync_vault(ctx: Context<SyncVault>) -> Result<()> {
+        instructions::sync_vault(ctx)
+    }
+
+    pub fn skim(ctx: Context<Skim>) -> Result<()> {
+        instructions::skim(ctx)
     }
 
-    pub fn emit_price(ctx: Context<EmitPrice>) -> ProgramResult {
-        let price = get_price(&ctx.accounts.token_vault, &ctx.accounts.staking_account);
+    pub fn emit_price(ctx: Context<EmitPrice>) -> Result<()> {
+        let price = get_price(&ctx.accounts.staking, &ctx.accounts.x_token_mint);
         emit!(Price {
-            step_per_xstep_e9: price.0,
-            step_per_xstep: price.1,
+            step_per_xstep_e18: price.scaled(Rounding::Down)?,
         });
         Ok(())
     }
 
-    pub fn emit_reward(ctx: Context<EmitReward>) -> ProgramResult {
-        let total_token = ctx.accounts.token_vault.amount;
-        let total_x_token = ctx.accounts.staking_account.total_x_token;
-        let reward: u64 = (ctx.accounts.user_staking_account.x_token_amount as u128)
-            .checked_mul(total_token as u128)
-            .unwrap()
-            .checked_div(total_x_token as u128)
-            .unwrap()
-            .checked_sub(ctx.accounts.user_staking_account.amount as u128)
-            .unwrap()
-            .try_into()
-            .unwrap();
+    pub fn emit_reward(ctx: Context<EmitReward>) -> Result<()> {
+        //rounding can leave the share value just below the deposit
+        let reward = get_price(&ctx.accounts.staking, &ctx.accounts.x_token_mint)
+            .to_tokens(ctx.accounts.position_escrow.amount, Rounding::Down)?
+            .saturating_sub(ctx.accounts.user_staking.amount);
         emit!(Reward {
-            deposit: ctx.accounts.user_staking_account.amount,
-            reward: reward,
+            deposit: ctx.accounts.user_staking.amount,
+            reward,
         });
         Ok(())
     }
 }
 
-// #[derive(Accounts)]
-// #[instruction(_nonce_vault: u8, _nonce_staking: u8)]
-// pub struct Initialize<'info> {
-//     #[account(
-//         address = constants::STEP_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap(),
-//     )]
-//     pub token_mint: Box<Account<'info, Mint>>,
-
-//     #[account(
-//         init,
-//         payer = initializer,
-//         token::mint = token_mint,
-//         token::authority = token_vault, //the PDA address is both the vault account and the authority (and event the mint authority)
-//         seeds = [ constants::STEP_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap().as_ref() ],
-//         bump = _nonce_vault,
-//     )]
-//     ///the not-yet-created, derived token vault pubkey
-//     pub token_vault: Box<Account<'info, TokenAccount>>,
-
-//     #[account(
-//         init,
-//         payer = initializer,
-//         seeds = [ constants::STAKING_PDA_SEED.as_ref() ],
-//         bump = _nonce_staking,
-//     )]
-//     pub staking_account: ProgramAccount<'info, StakingAccount>,
-
-//     #[account(mut)]
-//     ///pays rent on the initializing accounts
-//     pub initializer: Signer<'info>,
-
-//     ///used by anchor for init of the token
-//     pub system_program: Program<'info, System>,
-//     pub token_program: Program<'info, Token>,
-//     pub rent: Sysvar<'info, Rent>,
-// }
-
-#[derive(Accounts)]
-#[instruction(_nonce_staking: u8)]
-pub struct UpdateLockEndDate<'info> {
-    pub initializer: Signer<'info>,
-
-    #[account(
-        mut,
-        seeds = [ constants::STAKING_PDA_SEED.as_ref() ],
-        bump = _nonce_staking,
-        constraint = staking_account.initializer_key == *initializer.key,
-    )]
-    pub staking_account: ProgramAccount<'info, StakingAccount>,
-}
-
 #[derive(Accounts)]
-#[instruction(_nonce_staking: u8)]
-pub struct FreezeProgram<'info> {
+pub struct SetEmergencyMode<'info> {
     pub initializer: Signer<'info>,
 
     #[account(
         mut,
-        seeds = [ constants::STAKING_PDA_SEED.as_ref() ],
-        bump = _nonce_staking,
-        constraint = staking_account.initializer_key == *initializer.key,
+        seeds = [ constants::STAKING_PDA_SEED, staking.stake_token_mint.as_ref() ],
+        bump,
+        constraint = staking.initializer_key == *initializer.key @ ErrorCode::Unauthorized,
     )]
-    pub staking_account: ProgramAccount<'info, StakingAccount>,
+    pub staking: Account<'info, StakingAccount>,
 }
 
 #[derive(Accounts)]
-#[instruction(nonce_vault: u8, _nonce_staking: u8, amount: u64)]
-pub struct UnstakeAdmin<'info> {
-    #[account(
-        address = constants::STEP_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap(),
-    )]
-    pub token_mint: Box<Account<'info, Mint>>,
-
-    //the authority allowed to transfer from x_token_from
-    #[account(constraint = staking_account.initializer_key == *admin.key)]
-    pub admin: Signer<'info>,
-
-    #[account(
-        mut,
-        seeds = [ token_mint.key().as_ref() ],
-        bump = nonce_vault,
-    )]
-    pub token_vault: Box<Account<'info, TokenAccount>>,
+pub struct SetPause<'info> {
+    pub authority: Signer<'info>,
 
     #[account(
         mut,
-        seeds = [ constants::STAKING_PDA_SEED.as_ref() ],
-        bump = _nonce_staking,
-        constraint = !staking_account.freeze_program,
-    )]
-    pub staking_account: ProgramAccount<'info, StakingAccount>,
-
-    #[account(
-        mut,
-        constraint = user_staking_account.x_token_amount >= amount
+        seeds = [ constants::STAKING_PDA_SEED, staking.stake_token_mint.as_ref() ],
+        bump,
+        constraint = staking.is_pauser(authority.key) @ ErrorCode::Unauthorized,
     )]
-    pub user_staking_account: ProgramAccount<'info, UserStakingAccount>,
-
-    #[account(mut)]
-    //the token account to send token
-    pub token_to: Box<Account<'info, TokenAccount>>,
-
-    pub token_program: Program<'info, Token>,
+    pub staking: Account<'info, StakingAccount>,
 }
 
 #[derive(Accounts)]
 pub struct EmitPrice<'info> {
-    #[account(
-        address = constants::STEP_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap(),
-    )]
-    pub token_mint: Box<Account<'info, Mint>>,
+    pub stake_token_mint: Box<InterfaceAccount<'info, token_interface::Mint>>,
 
     #[account(
-        seeds = [ token_mint.key().as_ref() ],
+        seeds = [ constants::STAKING_PDA_SEED, stake_token_mint.key().as_ref() ],
         bump,
     )]
-    pub token_vault: Box<Account<'info, TokenAccount>>,
+    pub staking: Account<'info, StakingAccount>,
 
-    #[account(
-        seeds = [ constants::STAKING_PDA_SEED.as_ref() ],
-        bump,
-    )]
-    pub staking_account: ProgramAccount<'info, StakingAccount>,
+    #[account(address = staking.x_token_mint)]
+    pub x_token_mint: Box<InterfaceAccount<'info, token_interface::Mint>>,
 }
 
 #[derive(Accounts)]
 pub struct EmitReward<'info> {
-    #[account(
-        address = constants::STEP_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap(),
-    )]
-    pub token_mint: Box<Account<'info, Mint>>,
+    pub stake_token_mint: Box<InterfaceAccount<'info, token_interface::Mint>>,
 
     #[account(
-        seeds = [ token_mint.key().as_ref() ],
+        seeds = [ constants::STAKING_PDA_SEED, stake_token_mint.key().as_ref() ],
         bump,
     )]
-    pub token_vault: Box<Account<'info, TokenAccount>>,
+    pub staking: Account<'info, StakingAccount>,
+
+    /// CHECK: only used to derive the user staking address
+    pub user: UncheckedAccount<'info>,
 
     #[account(
-        seeds = [ constants::STAKING_PDA_SEED.as_ref() ],
+        seeds = [ staking.key().as_ref(), user.key().as_ref() ],
         bump,
     )]
-    pub staking_account: ProgramAccount<'info, StakingAccount>,
+    pub user_staking: Account<'info, UserStakingAccount>,
 
-    pub token_from_authority: AccountInfo<'info>,
+    #[account(address = staking.x_token_mint)]
+    pub x_token_mint: Box<InterfaceAccount<'info, token_interface::Mint>>,
 
     #[account(
-        seeds = [ token_from_authority.key().as_ref() ],
+        seeds = [ crate::constants::POSITION_ESCROW_SEED, user_staking.key().as_ref() ],
         bump,
     )]
-    pub user_staking_account: ProgramAccount<'info, UserStakingAccount>,
+    pub position_escrow: Box<InterfaceAccount<'info, token_interface::TokenAccount>>,
 }
 
 #[account]
 #[derive(Default)]
 pub struct StakingAccount {
+    /// the pool admin, rotated with propose_admin and accept_admin
     pub initializer_key: Pubkey,
-    pub lock_end_date: u64,
-    pub total_x_token: u64,
-    pub freeze_program: bool,
+    /// the mint this pool stakes, also part of the pool address
+    pub stake_token_mint: Pubkey,
+    /// the pool owned x token mint, the vault is its mint authority
+    pub x_token_mint: Pubkey,
+    /// lock options offered to stakers, longer locks earn boosted reward weight
+    pub lock_tiers: [LockTier; MAX_LOCK_TIERS],
+    /// PAUSE_* bits, each one blocks a single group of instructions
+    pub pause_flags: u8,
+    /// fee charged on every stake, in basis points of the staked amount
+    pub stake_fee_bps: u16,
+    /// reward tokens dripped from the reserve into the vault per second,
+    /// shared by reward weight and compounded into the position escrows
+    pub emission_rate: u64,
+    pub last_emission_ts: u64,
+    /// emitted rewards not yet moved into the vault
+    pub pending_emission: u64,
+    /// emitted tokens per reward share, scaled by REWARD_PRECISION
+    pub emission_per_share: u128,
+    /// emitted tokens in the vault the positions have not compounded yet
+    pub emission_owed: u64,
+    /// additional reward tokens paid out by claim_rewards
+    pub rewards: [RewardInfo; MAX_REWARDS],
+    /// sum of all position reward shares
+    pub total_reward_shares: u64,
+    /// seconds between request_unstake and withdraw, non zero disables unstake
+    pub cooldown_duration: u64,
+    pub early_unstake_penalty: EarlyUnstakePenalty,
+    /// admin proposed by propose_admin, the default key when none is pending
+    pub pending_admin: Pubkey,
+    /// may freeze and unfreeze the pool
+    pub pauser: Pubkey,
+    /// may queue stake fee and early unstake penalty changes
+    pub fee_manager: Pubkey,
+    /// may queue lock tier, cooldown, emission and reward rate changes
+    pub parameter_manager: Pubkey,
+    /// seconds a queued parameter change waits before execute_change
+    pub timelock_delay: u64,
+    /// blocks staking, releasing and unstaking and opens the emergency exits
+    pub emergency_mode: bool,
+    /// stake tokens the pool accounts for, the exchange rate ignores anything
+    /// else sent to the vault
+    pub total_staked: u64,
+}
+
+impl StakingAccount {
+    pub const LEN: usize = 8
+        + 32
+        + 32
+        + 32
+        + LockTier::LEN * MAX_LOCK_TIERS
+        + 1
+        + 2
+        + 8
+        + 8
+        + 8
+        + 16
+        + 8
+        + RewardInfo::LEN * MAX_REWARDS
+        + 8
+        + 8
+        + EarlyUnstakePenalty::LEN
+        + 32
+        + 32
+        + 32
+        + 32
+        + 8
+        + 1
+        + 8;
+
+    /// the vault must always hold at least the accounted balance and the
+    /// emission owed to the positions
+    pub fn check_backing(&self, vault_amount: u64) -> Result<()> {
+        require!(
+            self.total_staked.safe_add(self.emission_owed)? <= vault_amount,
+            ErrorCode::VaultShortfall
+        );
+
+        Ok(())
+    }
+
+    /// tokens sent straight to the vault, neither staked nor owed
+    pub fn surplus(&self, vault_amount: u64) -> Result<u64> {
+        self.check_backing(vault_amount)?;
+
+        vault_amount
+            .safe_sub(self.total_staked)?
+            .safe_sub(self.emission_owed)
+    }
+
+    /// what `shares` are worth in emergency mode, valued against the tokens
+    /// left in the vault so a shortfall is shared pro rata
+    pub fn emergency_value(
+        &self,
+        vault_amount: u64,
+        x_token_supply: u64,
+        shares: u64,
+    ) -> Result<u64> {
+        ExchangeRate::new(self.total_staked.min(vault_amount), x_token_supply)
+            .to_tokens(shares, Rounding::Down)
+    }
+
+    /// what the escrowed `shares` of a position backed by `principal` pay out
+    /// in emergency mode, their emergency value capped at the principal
+    pub fn emergency_payout(
+        &self,
+        vault_amount: u64,
+        x_token_supply: u64,
+        shares: u64,
+        principal: u64,
+    ) -> Result<u64> {
+        let value = self.emergency_value(vault_amount, x_token_supply, shares)?;
+
+        Ok(value.min(principal))
+    }
+
+    pub fn is_paused(&self, flag: u8) -> bool {
+        self.pause_flags & flag != 0
+    }
+
+    //the admin holds every role, delegated roles are unset by default
+    pub fn is_pauser(&self, key: &Pubkey) -> bool {
+        *key == self.initializer_key || *key == self.pauser
+    }
+
+    pub fn is_fee_manager(&self, key: &Pubkey) -> bool {
+        *key == self.initializer_key || *key == self.fee_manager
+    }
+
+    pub fn is_parameter_manager(&self, key: &Pubkey) -> bool {
+        *key == self.initializer_key || *key == self.parameter_manager
+    }
+
+    pub fn accrue_emission(&mut self, now_ts: u64) {
+        let elapsed = now_ts.saturating_sub(self.last_emission_ts);
+        self.pending_emission = self
+            .pending_emission
+            .saturating_add(elapsed.saturating_mul(self.emission_rate));
+        self.last_emission_ts = now_ts;
+    }
+
+    pub fn set_lock_tiers(&mut self, lock_tiers: [LockTier; MAX_LOCK_TIERS]) -> Result<()> {
+        validate_lock_tiers(&lock_tiers)?;
+        self.lock_tiers = lock_tiers;
+
+        Ok(())
+    }
+
+    pub fn may_change(&self, change: &ParameterChange, key: &Pubkey) -> bool {
+        match change {
+            ParameterChange::StakeFee(_) | ParameterChange::EarlyUnstakePenalty(_) => {
+                self.is_fee_manager(key)
+            }
+            ParameterChange::TimelockDelay(_) => *key == self.initializer_key,
+            _ => self.is_parameter_manager(key),
+        }
+    }
+
+    pub fn validate_change(&self, change: &ParameterChange) -> Result<()> {
+        match change {
+            ParameterChange::LockTiers(lock_tiers) => validate_lock_tiers(lock_tiers)?,
+            ParameterChange::StakeFee(fee_bps) => require!(
+                (*fee_bps as u64) <= BPS_DENOMINATOR,
+                ErrorCode::InvalidStakeFee
+            ),
+            ParameterChange::EarlyUnstakePenalty(penalty) => require!(
+                (penalty.penalty_bps as u64) <= BPS_DENOMINATOR,
+                ErrorCode::InvalidPenalty
+            ),
+            ParameterChange::RewardRate { index, .. } => require!(
+                (*index as usize) < MAX_REWARDS
+                    && self.rewards[*index as usize].mint != Pubkey::default(),
+                ErrorCode::InvalidRewardIndex
+            ),
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    pub fn apply_change(&mut self, change: &ParameterChange, now_ts: u64) -> Result<()> {
+        self.validate_change(change)?;
+
+        match *change {
+            ParameterChange::LockTiers(lock_tiers) => self.lock_tiers = lock_tiers,
+            ParameterChange::StakeFee(fee_bps) => self.stake_fee_bps = fee_bps,
+            ParameterChange::EarlyUnstakePenalty(penalty) => self.early_unstake_penalty = penalty,
+            ParameterChange::CooldownDuration(duration) => self.cooldown_duration = duration,
+            ParameterChange::EmissionRate(rate) => {
+                //settle the elapsed time at the old rate
+                self.accrue_emission(now_ts);
+                self.emission_rate = rate;
+            }
+            ParameterChange::RewardRate { index, rate } => {
+                //settle the elapsed time at the old rate
+                self.update_rewards(now_ts)?;
+                self.rewards[index as usize].rate = rate;
+            }
+            ParameterChange::TimelockDelay(delay) => self.timelock_delay = delay,
+        }
+
+        Ok(())
+    }
+
+    pub fn update_rewards(&mut self, now_ts: u64) -> Result<()> {
+        let total_reward_shares = self.total_reward_shares;
+
+        for reward in self.rewards.iter_mut() {
+            if reward.mint == Pubkey::default() {
+                continue;
+            }
+
+            let elapsed = now_ts.saturating_sub(reward.last_update_ts);
+            if total_reward_shares > 0 && elapsed > 0 {
+                reward.reward_per_share = (elapsed as u128)
+                    .safe_mul(reward.rate as u128)?
+                    .safe_mul(REWARD_PRECISION)?
+                    .safe_div(total_reward_shares as u128)?
+                    .safe_add(reward.reward_per_share)?;
+            }
+            reward.last_update_ts = now_ts;
+        }
+
+        Ok(())
+    }
+}
+
+pub const MAX_LOCK_TIERS: usize = 4;
+pub const MAX_REWARDS: usize = 3;
+pub const REWARD_PRECISION: u128 = 1_000_000_000_000;
+pub const PAUSE_STAKE: u8 = 1 << 0;
+pub const PAUSE_UNSTAKE: u8 = 1 << 1;
+pub const PAUSE_CLAIM: u8 = 1 << 2;
+pub const PAUSE_ADMIN_WITHDRAW: u8 = 1 << 3;
+pub const PAUSE_ALL: u8 = PAUSE_STAKE | PAUSE_UNSTAKE | PAUSE_CLAIM | PAUSE_ADMIN_WITHDRAW;
+/// positions with no more escrowed shares or owed rewards than this can be swept
+pub const DUST_THRESHOLD: u64 = 1_000;
+pub const DEFAULT_TIMELOCK_DELAY: u64 = 2 * 24 * 60 * 60;
+
+fn validate_lock_tiers(lock_tiers: &[LockTier; MAX_LOCK_TIERS]) -> Result<()> {
+    for tier in lock_tiers.iter() {
+        require!(
+            tier.multiplier_bps == 0 || (tier.multiplier_bps as u64) >= BPS_DENOMINATOR,
+            ErrorCode::InvalidLockTier
+        );
+    }
+
+    Ok(())
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct LockTier {
+    /// seconds a deposit stays locked after it is made
+    pub duration: u64,
+    /// reward weight multiplier in basis points, zero disables the tier
+    pub multiplier_bps: u16,
+}
+
+impl LockTier {
+    pub const LEN: usize = 8 + 2;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct EarlyUnstakePenalty {
+    /// penalty in basis points of the unstaked tokens
+    pub penalty_bps: u16,
+    /// scale the penalty down linearly over the remaining lock
+    pub decay: bool,
+    /// send the penalty to the fee receiver instead of leaving it to the stakers
+    pub to_fee_receiver: bool,
+}
+
+impl EarlyUnstakePenalty {
+    pub const LEN: usize = 2 + 1 + 1;
+}
+
+/// a parameter update that has to wait out the pool timelock
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub enum ParameterChange {
+    LockTiers([LockTier; MAX_LOCK_TIERS]),
+    StakeFee(u16),
+    EarlyUnstakePenalty(EarlyUnstakePenalty),
+    CooldownDuration(u64),
+    EmissionRate(u64),
+    RewardRate { index: u8, rate: u64 },
+    TimelockDelay(u64),
+}
+
+impl ParameterChange {
+    //the largest variant is the lock tier table
+    pub const LEN: usize = 1 + LockTier::LEN * MAX_LOCK_TIERS;
+
+    /// one pending change per kind, used in the pending change address
+    pub fn kind(&self) -> u8 {
+        match self {
+            ParameterChange::LockTiers(_) => 0,
+            ParameterChange::StakeFee(_) => 1,
+            ParameterChange::EarlyUnstakePenalty(_) => 2,
+            ParameterChange::CooldownDuration(_) => 3,
+            ParameterChange::EmissionRate(_) => 4,
+            ParameterChange::RewardRate { .. } => 5,
+            ParameterChange::TimelockDelay(_) => 6,
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct RewardInfo {
+    /// the reward mint, default when the slot is unused
+    pub mint: Pubkey,
+    pub vault: Pubkey,
+    /// reward tokens distributed per second
+    pub rate: u64,
+    /// rewards per reward share, scaled by REWARD_PRECISION
+    pub reward_per_share: u128,
+    pub last_update_ts: u64,
+}
+
+impl RewardInfo {
+    pub const LEN: usize = 32 + 32 + 8 + 16 + 8;
 }
 
 #[account]
 #[derive(Default)]
 pub struct UserStakingAccount {
     pub amount: u64,
-    pub x_token_amount: u64,
+    /// unix timestamp before which the escrowed shares can not be released
+    pub lock_end_date: u64,
+    /// lock tier chosen on the latest deposit
+    pub lock_tier: u8,
+    /// weight of the escrowed shares in the emission and the additional rewards
+    pub reward_shares: u64,
+    pub reward_debts: [u128; MAX_REWARDS],
+    pub rewards_owed: [u64; MAX_REWARDS],
+    pub emission_debt: u128,
+    /// emitted tokens earned but not compounded into the escrow yet
+    pub emission_owed: u64,
+    /// requests ever made, the next request address is derived from it
+    pub unstake_request_count: u64,
+    /// requests not withdrawn yet, the position can not be closed before
+    pub open_unstake_requests: u64,
+}
+
+impl UserStakingAccount {
+    pub const LEN: usize = 8 + 8 + 8 + 1 + 8 + 16 * MAX_REWARDS + 8 * MAX_REWARDS + 16 + 8 + 8 + 8;
+
+    pub fn add_reward_shares(
+        &mut self,
+        staking: &mut StakingAccount,
+        shares: u64,
+        now_ts: u64,
+    ) -> Result<()> {
+        staking.update_rewards(now_ts)?;
+        self.settle_rewards(staking)?;
+        self.reward_shares = self.reward_shares.safe_add(shares)?;
+        staking.total_reward_shares = staking.total_reward_shares.safe_add(shares)?;
+        self.reset_reward_debts(staking)
+    }
+
+    /// takes `shares` of the `escrowed` shares out of the position together
+    /// with their part of its reward weight and principal, returns that principal
+    pub fn remove_shares(
+        &mut self,
+        staking: &mut StakingAccount,
+        shares: u64,
+        escrowed: u64,
+        now_ts: u64,
+    ) -> Result<u64> {
+        //round against the position, the last shares take whatever is left
+        let (reward_shares, amount) = if shares >= escrowed {
+            (self.reward_shares, self.amount)
+        } else {
+            (
+                mul_div_up(self.reward_shares, shares, escrowed)?,
+                mul_div_up(self.amount, shares, escrowed)?,
+            )
+        };
+
+        staking.update_rewards(now_ts)?;
+        self.settle_rewards(staking)?;
+        self.reward_shares = self.reward_shares.safe_sub(reward_shares)?;
+        staking.total_reward_shares = staking.total_reward_shares.safe_sub(reward_shares)?;
+        self.reset_reward_debts(staking)?;
+
+        self.amount = self.amount.safe_sub(amount)?;
+
+        Ok(amount)
+    }
+
+    /// moves the rewards accrued since the last settlement into rewards_owed
+    /// and the emission into emission_owed
+    pub fn settle_rewards(&mut self, staking: &StakingAccount) -> Result<()> {
+        let emitted = to_u64(
+            (self.reward_shares as u128)
+                .safe_mul(staking.emission_per_share)?
+                .safe_div(REWARD_PRECISION)?
+                .safe_sub(self.emission_debt)?,
+        )?;
+        self.emission_owed = self.emission_owed.safe_add(emitted)?;
+
+        for (i, reward) in staking.rewards.iter().enumerate() {
+            let accrued = to_u64(
+                (self.reward_shares as u128)
+                    .safe_mul(reward.reward_per_share)?
+                    .safe_div(REWARD_PRECISION)?
+                    .safe_sub(self.reward_debts[i])?,
+            )?;
+            self.rewards_owed[i] = self.rewards_owed[i].safe_add(accrued)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn reset_reward_debts(&mut self, staking: &StakingAccount) -> Result<()> {
+        self.emission_debt = (self.reward_shares as u128)
+            .safe_mul(staking.emission_per_share)?
+            .safe_div(REWARD_PRECISION)?;
+
+        for (i, reward) in staking.rewards.iter().enumerate() {
+            self.reward_debts[i] = (self.reward_shares as u128)
+                .safe_mul(reward.reward_per_share)?
+                .safe_div(REWARD_PRECISION)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[account]
+#[derive(Default)]
+pub struct UnstakeRequest {
+    pub user: Pubkey,
+    pub staking: Pubkey,
+    /// tokens released by withdraw
+    pub amount: u64,
+    /// unix timestamp from which the request can be withdrawn
+    pub maturity: u64,
+}
+
+impl UnstakeRequest {
+    pub const LEN: usize = 8 + 32 + 32 + 8 + 8;
+}
+
+#[account]
+pub struct PendingChange {
+    pub staking: Pubkey,
+    /// paid the rent, refunded when the change is executed or cancelled
+    pub proposer: Pubkey,
+    pub change: ParameterChange,
+    /// unix timestamp from which the change can be executed
+    pub eta: u64,
+}
+
+impl PendingChange {
+    pub const LEN: usize = 8 + 32 + 32 + ParameterChange::LEN + 8;
 }
 
 #[event]
 pub struct PriceChange {
-    pub old_step_per_xstep_e9: u64,
-    pub old_step_per_xstep: String,
-    pub new_step_per_xstep_e9: u64,
-    pub new_step_per_xstep: String,
+    /// tokens per x token scaled by 1e18, before and after the change
+    pub old_step_per_xstep_e18: u128,
+    pub new_step_per_xstep_e18: u128,
+}
+
+#[event]
+pub struct StakeFee {
+    pub user: Pubkey,
+    pub stake_amount: u64,
+    pub fee: u64,
+}
+
+#[event]
+pub struct StakedFor {
+    pub payer: Pubkey,
+    pub beneficiary: Pubkey,
+    pub stake_amount: u64,
+    pub x_token_amount: u64,
+}
+
+#[event]
+pub struct RewardEmission {
+    pub amount: u64,
+}
+
+#[event]
+pub struct RewardClaimed {
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct UnstakedTo {
+    pub user: Pubkey,
+    pub recipient: Pubkey,
+    pub x_token_amount: u64,
+    pub amount: u64,
+}
+
+#[event]
+pub struct EarlyUnstake {
+    pub user: Pubkey,
+    pub x_token_amount: u64,
+    pub amount: u64,
+    pub penalty: u64,
+}
+
+#[event]
+pub struct UnstakeRequested {
+    pub user: Pubkey,
+    pub request: Pubkey,
+    pub x_token_amount: u64,
+    pub amount: u64,
+    pub maturity: u64,
+}
+
+#[event]
+pub struct Withdrawn {
+    pub user: Pubkey,
+    pub request: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct AdminProposed {
+    pub admin: Pubkey,
+    pub pending_admin: Pubkey,
+}
+
+#[event]
+pub struct AdminChanged {
+    pub old_admin: Pubkey,
+    pub new_admin: Pubkey,
+}
+
+#[event]
+pub struct RolesChanged {
+    pub pauser: Pubkey,
+    pub fee_manager: Pubkey,
+    pub parameter_manager: Pubkey,
+}
+
+#[event]
+pub struct ChangeQueued {
+    pub pending_change: Pubkey,
+    pub change: ParameterChange,
+    pub eta: u64,
+}
+
+#[event]
+pub struct ChangeExecuted {
+    pub pending_change: Pubkey,
+    pub change: ParameterChange,
+}
+
+#[event]
+pub struct ChangeCancelled {
+    pub pending_change: Pubkey,
+    pub change: ParameterChange,
+}
+
+#[event]
+pub struct EmergencyModeChanged {
+    pub enabled: bool,
+}
+
+#[event]
+pub struct EmergencyWithdrawn {
+    pub user: Pubkey,
+    pub x_token_amount: u64,
+    pub principal: u64,
+    pub amount: u64,
+}
+
+#[event]
+pub struct EmergencyUnstaked {
+    pub user: Pubkey,
+    pub x_token_amount: u64,
+    pub amount: u64,
+}
+
+#[event]
+pub struct VaultSynced {
+    pub amount: u64,
+}
+
+#[event]
+pub struct VaultSkimmed {
+    pub amount: u64,
+    pub token_to: Pubkey,
+}
+
+#[event]
+pub struct AdminForcedExit {
+    pub admin: Pubkey,
+    pub user: Pubkey,
+    pub reason: u8,
+    pub x_token_amount: u64,
+    pub amount: u64,
+}
+
+#[event]
+pub struct PositionClosed {
+    pub user: Pubkey,
+    pub swept: bool,
+}
+
+#[event]
+pub struct PauseChanged {
+    pub old_flags: u8,
+    pub new_flags: u8,
 }
 
 #[event]
 pub struct Price {
-    pub step_per_xstep_e9: u64,
-    pub step_per_xstep: String,
+    /// tokens per x token scaled by 1e18
+    pub step_per_xstep_e18: u128,
 }
 
 #[event]
@@ -366,8 +935,58 @@ pub struct Reward {
     pub reward: u64,
 }
 
-#[error]
+#[error_code]
 pub enum ErrorCode {
     #[msg("Not exceed lock end date")]
     NotExceedLockEndDate,
+    #[msg("Stake fee exceeds 100%")]
+    InvalidStakeFee,
+    #[msg("Invalid lock tier")]
+    InvalidLockTier,
+    #[msg("All reward slots are in use")]
+    RewardSlotsFull,
+    #[msg("Reward mint is already registered")]
+    DuplicateReward,
+    #[msg("Invalid reward index")]
+    InvalidRewardIndex,
+    #[msg("Reward account does not match the registered reward")]
+    InvalidRewardAccount,
+    #[msg("Math overflow")]
+    MathOverflow,
+    #[msg("Math underflow")]
+    MathUnderflow,
+    #[msg("Division by a zero supply")]
+    ZeroSupply,
+    #[msg("Amount must be greater than zero")]
+    ZeroAmount,
+    #[msg("Slippage tolerance exceeded")]
+    SlippageExceeded,
+    #[msg("Operation is paused")]
+    Paused,
+    #[msg("Signer does not hold the required role")]
+    Unauthorized,
+    #[msg("Not enough x tokens")]
+    InsufficientShares,
+    #[msg("Pool requires request_unstake and withdraw")]
+    CooldownRequired,
+    #[msg("Cooldown has not elapsed")]
+    CooldownNotElapsed,
+    #[msg("Early unstake penalty exceeds 100%")]
+    InvalidPenalty,
+    #[msg("Timelock has not elapsed")]
+    TimelockNotElapsed,
+    #[msg("Unknown pause flags")]
+    InvalidPauseFlags,
+    #[msg("Pool is in emergency mode")]
+    EmergencyMode,
+    #[msg("Pool is not in emergency mode")]
+    NotEmergencyMode,
+    #[msg("Position still holds shares or rewards")]
+    PositionNotEmpty,
+    #[msg("Vault holds less than the accounted balance")]
+    VaultShortfall,
+    #[msg("A forced exit needs a non zero reason code")]
+    MissingReason,
+    #[msg("Only the position owner can extend the lock of its shares")]
+    LockExtensionDenied,
 }
@@ -0,0 +1,210 @@
+use anchor_lang::prelude::*;
+
+use crate::ErrorCode;
+
+/// checked arithmetic that fails with a program error instead of panicking
+pub trait SafeMath: Sized {
+    fn safe_add(self, rhs: Self) -> Result<Self>;
+    fn safe_sub(self, rhs: Self) -> Result<Self>;
+    fn safe_mul(self, rhs: Self) -> Result<Self>;
+    fn safe_div(self, rhs: Self) -> Result<Self>;
+}
+
+macro_rules! impl_safe_math {
+    ($t:ty) => {
+        impl SafeMath for $t {
+            fn safe_add(self, rhs: Self) -> Result<Self> {
+                self.checked_add(rhs)
+                    .ok_or_else(|| error!(ErrorCode::MathOverflow))
+            }
+
+            fn safe_sub(self, rhs: Self) -> Result<Self> {
+                self.checked_sub(rhs)
+                    .ok_or_else(|| error!(ErrorCode::MathUnderflow))
+            }
+
+            fn safe_mul(self, rhs: Self) -> Result<Self> {
+                self.checked_mul(rhs)
+                    .ok_or_else(|| error!(ErrorCode::MathOverflow))
+            }
+
+            fn safe_div(self, rhs: Self) -> Result<Self> {
+                self.checked_div(rhs)
+                    .ok_or_else(|| error!(ErrorCode::ZeroSupply))
+            }
+        }
+    };
+}
+
+impl_safe_math!(u64);
+impl_safe_math!(u128);
+
+/// computes `a * b / denominator` without intermediate overflow
+pub fn mul_div(a: u64, b: u64, denominator: u64) -> Result<u64> {
+    to_u64(
+        (a as u128)
+            .safe_mul(b as u128)?
+            .safe_div(denominator as u128)?,
+    )
+}
+
+/// computes `a * b / denominator` rounded up
+pub fn mul_div_up(a: u64, b: u64, denominator: u64) -> Result<u64> {
+    to_u64(div_round(
+        (a as u128).safe_mul(b as u128)?,
+        denominator as u128,
+        Rounding::Up,
+    )?)
+}
+
+/// virtual tokens and shares on both sides of the exchange rate, so a tiny
+/// first deposit cannot be used to inflate the share price
+pub const VIRTUAL_TOKENS: u64 = 1_000_000;
+pub const VIRTUAL_SHARES: u64 = 1_000_000;
+
+/// scale of the fixed-point rate reported by ExchangeRate::scaled
+pub const RATE_PRECISION: u128 = 1_000_000_000_000_000_000;
+
+/// conversions always round against the caller so dust can never be pulled
+/// out of the vault: minted shares and paid tokens round down, shares burned
+/// for an exact token amount round up
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rounding {
+    Down,
+    Up,
+}
+
+/// tokens per share of a pool, kept as the exact ratio of its virtual
+/// inclusive totals so conversions lose at most one unit to rounding
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExchangeRate {
+    tokens: u128,
+    shares: u128,
+}
+
+impl ExchangeRate {
+    pub fn new(total_staked: u64, total_shares: u64) -> Self {
+        Self {
+            tokens: total_staked as u128 + VIRTUAL_TOKENS as u128,
+            shares: total_shares as u128 + VIRTUAL_SHARES as u128,
+        }
+    }
+
+    /// shares worth `amount` tokens
+    pub fn to_shares(&self, amount: u64, rounding: Rounding) -> Result<u64> {
+        to_u64(div_round(
+            (amount as u128).safe_mul(self.shares)?,
+            self.tokens,
+            rounding,
+        )?)
+    }
+
+    /// tokens backing `shares`
+    pub fn to_tokens(&self, shares: u64, rounding: Rounding) -> Result<u64> {
+        to_u64(div_round(
+            (shares as u128).safe_mul(self.tokens)?,
+            self.shares,
+            rounding,
+        )?)
+    }
+
+    /// tokens per share scaled by RATE_PRECISION
+    pub fn scaled(&self, rounding: Rounding) -> Result<u128> {
+        div_round(self.tokens.safe_mul(RATE_PRECISION)?, self.shares, rounding)
+    }
+}
+
+fn div_round(numerator: u128, denominator: u128, rounding: Rounding) -> Result<u128> {
+    let quotient = numerator.safe_div(denominator)?;
+    let remainder = numerator % denominator;
+    if rounding == Rounding::Up && remainder != 0 {
+        return quotient.safe_add(1);
+    }
+
+    Ok(quotient)
+}
+
+pub fn to_u64(value: u128) -> Result<u64> {
+    value
+        .try_into()
+        .map_err(|_| error!(ErrorCode::MathOverflow))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn div_round_rounds_only_inexact_quotients_up() {
+        assert_eq!(div_round(10, 3, Rounding::Down).unwrap(), 3);
+        assert_eq!(div_round(10, 3, Rounding::Up).unwrap(), 4);
+        assert_eq!(div_round(9, 3, Rounding::Down).unwrap(), 3);
+        assert_eq!(div_round(9, 3, Rounding::Up).unwrap(), 3);
+        assert!(div_round(1, 0, Rounding::Up).is_err());
+    }
+
+    #[test]
+    fn mul_div_up_rounds_up() {
+        assert_eq!(mul_div(7, 3, 2).unwrap(), 10);
+        assert_eq!(mul_div_up(7, 3, 2).unwrap(), 11);
+        assert_eq!(mul_div_up(4, 3, 2).unwrap(), 6);
+    }
+
+    #[test]
+    fn empty_pool_converts_one_to_one() {
+        let rate = ExchangeRate::new(0, 0);
+
+        assert_eq!(rate.to_shares(1_000, Rounding::Down).unwrap(), 1_000);
+        assert_eq!(rate.to_tokens(1_000, Rounding::Down).unwrap(), 1_000);
+        assert_eq!(rate.scaled(Rounding::Down).unwrap(), RATE_PRECISION);
+    }
+
+    #[test]
+    fn virtual_offsets_blunt_a_donated_first_deposit() {
+        //one share minted, then a large donation folded into the balance
+        let rate = ExchangeRate::new(1_000_001, 1);
+
+        //without the offsets the second depositor would get zero shares
+        let shares = rate.to_shares(1_000, Rounding::Down).unwrap();
+        assert_eq!(shares, 500);
+        assert_eq!(
+            rate.scaled(Rounding::Down).unwrap(),
+            2_000_001 * RATE_PRECISION / 1_000_001
+        );
+    }
+
+    #[test]
+    fn conversions_round_in_the_requested_direction() {
+        //3 tokens per 2 shares including the offsets
+        let rate = ExchangeRate::new(2_000_000, 1_000_000);
+
+        assert_eq!(rate.to_shares(100, Rounding::Down).unwrap(), 66);
+        assert_eq!(rate.to_shares(100, Rounding::Up).unwrap(), 67);
+        assert_eq!(rate.to_tokens(101, Rounding::Down).unwrap(), 151);
+        assert_eq!(rate.to_tokens(101, Rounding::Up).unwrap(), 152);
+    }
+
+    #[test]
+    fn round_trip_never_returns_more_than_deposited() {
+        let totals = [0, 1, 7, 999_999, 1_000_000, 123_456_789, u32::MAX as u64];
+        let amounts = [1, 2, 3, 10, 999, 1_000_001, 987_654_321];
+
+        for total_staked in totals {
+            for total_shares in totals {
+                for amount in amounts {
+                    let rate = ExchangeRate::new(total_staked, total_shares);
+                    let shares = rate.to_shares(amount, Rounding::Down).unwrap();
+
+                    //redeem against the totals after the deposit
+                    let after = ExchangeRate::new(total_staked + amount, total_shares + shares);
+                    let tokens = after.to_tokens(shares, Rounding::Down).unwrap();
+                    assert!(tokens <= amount);
+
+                    //burning for an exact amount always covers that amount
+                    let burned = rate.to_shares(amount, Rounding::Up).unwrap();
+                    assert!(rate.to_tokens(burned, Rounding::Down).unwrap() >= amount);
+                }
+            }
+        }
+    }
+}
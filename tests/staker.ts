@@ -1,16 +1,436 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
+import {
+  createAccount,
+  createAssociatedTokenAccountIdempotent,
+  createMint,
+  getAccount,
+  mintTo,
+  ASSOCIATED_TOKEN_PROGRAM_ID,
+  TOKEN_PROGRAM_ID,
+} from "@solana/spl-token";
+import { Keypair, PublicKey, SystemProgram } from "@solana/web3.js";
+import { assert } from "chai";
 import { Staker } from "../target/types/staker";
 
+const BPF_LOADER_UPGRADEABLE_PROGRAM_ID = new PublicKey(
+  "BPFLoaderUpgradeab1e11111111111111111111111"
+);
+
+const STAKE_FEE_RECEIVER = new PublicKey(
+  "AMyJM7SrZCQxFTNPGwPuyCHgSFa6u7WhDrJPXL3jyXyB"
+);
+
+const day = 24 * 60 * 60;
+const stakeAmount = 1_000_000;
+const lockTiers = [
+  { duration: new anchor.BN(0), multiplierBps: 10000 },
+  { duration: new anchor.BN(90 * day), multiplierBps: 11000 },
+  { duration: new anchor.BN(180 * day), multiplierBps: 12500 },
+  { duration: new anchor.BN(365 * day), multiplierBps: 15000 },
+];
+
 describe("staker", () => {
   // Configure the client to use the local cluster.
-  anchor.setProvider(anchor.AnchorProvider.env());
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
 
   const program = anchor.workspace.Staker as Program<Staker>;
+  const admin = (provider.wallet as anchor.Wallet).payer;
+
+  const pda = (...seeds: (Buffer | PublicKey)[]) =>
+    PublicKey.findProgramAddressSync(
+      seeds.map((seed) => (seed instanceof PublicKey ? seed.toBuffer() : seed)),
+      program.programId
+    )[0];
+
+  const [programData] = PublicKey.findProgramAddressSync(
+    [program.programId.toBuffer()],
+    BPF_LOADER_UPGRADEABLE_PROGRAM_ID
+  );
+
+  const poolAccounts = (mint: PublicKey) => ({
+    stakeTokenMint: mint,
+    stakeTokenVault: pda(mint),
+    staking: pda(Buffer.from("staking"), mint),
+    xTokenMint: pda(Buffer.from("x_token"), mint),
+    rewardReserve: pda(Buffer.from("reward_reserve"), mint),
+    tokenProgram: TOKEN_PROGRAM_ID,
+    xTokenProgram: TOKEN_PROGRAM_ID,
+  });
+
+  const initialize = (mint: PublicKey, initializer: Keypair) =>
+    program.methods
+      .initialize(lockTiers)
+      .accountsPartial({
+        initializer: initializer.publicKey,
+        ...poolAccounts(mint),
+        unbondingVault: pda(Buffer.from("unbonding_vault"), mint),
+        program: program.programId,
+        programData,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([initializer])
+      .rpc();
+
+  const fund = async (to: PublicKey) => {
+    const sig = await provider.connection.requestAirdrop(
+      to,
+      10 * anchor.web3.LAMPORTS_PER_SOL
+    );
+    await provider.connection.confirmTransaction(sig);
+  };
+
+  const expectError = async (tx: Promise<unknown>, code: string) => {
+    try {
+      await tx;
+    } catch (err) {
+      assert.equal(err.error?.errorCode?.code, code);
+      return;
+    }
+    assert.fail(`expected ${code}`);
+  };
+
+  const balance = async (account: PublicKey) =>
+    Number((await getAccount(provider.connection, account)).amount);
+
+  const createPool = async () => {
+    const mint = await createMint(
+      provider.connection,
+      admin,
+      admin.publicKey,
+      null,
+      6
+    );
+    await initialize(mint, admin);
+    const stakeFee = await createAssociatedTokenAccountIdempotent(
+      provider.connection,
+      admin,
+      mint,
+      STAKE_FEE_RECEIVER,
+      {},
+      TOKEN_PROGRAM_ID,
+      ASSOCIATED_TOKEN_PROGRAM_ID,
+      true
+    );
+    return { ...poolAccounts(mint), stakeFee };
+  };
+
+  type Pool = Awaited<ReturnType<typeof createPool>>;
+
+  const createStaker = async (pool: Pool) => {
+    const user = Keypair.generate();
+    await fund(user.publicKey);
+    const stakeTokenAccount = await createAccount(
+      provider.connection,
+      admin,
+      pool.stakeTokenMint,
+      user.publicKey
+    );
+    await mintTo(
+      provider.connection,
+      admin,
+      pool.stakeTokenMint,
+      stakeTokenAccount,
+      admin,
+      stakeAmount
+    );
+    const xTokenAccount = await createAccount(
+      provider.connection,
+      admin,
+      pool.xTokenMint,
+      user.publicKey
+    );
+    const userStaking = pda(pool.staking, user.publicKey);
+    const positionEscrow = pda(Buffer.from("position_escrow"), userStaking);
+    return {
+      user,
+      stakeTokenAccount,
+      xTokenAccount,
+      userStaking,
+      positionEscrow,
+    };
+  };
+
+  type Position = Awaited<ReturnType<typeof createStaker>>;
+
+  const stake = (pool: Pool, staker: Position, lockTier: number) =>
+    program.methods
+      .stake(new anchor.BN(stakeAmount), lockTier, new anchor.BN(0))
+      .accountsPartial({
+        deposit: {
+          user: staker.user.publicKey,
+          stakeTokenMint: pool.stakeTokenMint,
+          stakeTokenAccount: staker.stakeTokenAccount,
+          stakeTokenVault: pool.stakeTokenVault,
+          staking: pool.staking,
+          rewardReserve: pool.rewardReserve,
+          xTokenMint: pool.xTokenMint,
+          stakeFee: pool.stakeFee,
+          tokenProgram: pool.tokenProgram,
+          xTokenProgram: pool.xTokenProgram,
+        },
+        userStaking: staker.userStaking,
+        positionEscrow: staker.positionEscrow,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([staker.user])
+      .rpc();
+
+  const release = (pool: Pool, staker: Position, amount: number) =>
+    program.methods
+      .release(new anchor.BN(amount))
+      .accountsPartial({
+        user: staker.user.publicKey,
+        stakeTokenMint: pool.stakeTokenMint,
+        stakeTokenVault: pool.stakeTokenVault,
+        staking: pool.staking,
+        rewardReserve: pool.rewardReserve,
+        userStaking: staker.userStaking,
+        xTokenMint: pool.xTokenMint,
+        positionEscrow: staker.positionEscrow,
+        userXTokenAccount: staker.xTokenAccount,
+        tokenProgram: pool.tokenProgram,
+        xTokenProgram: pool.xTokenProgram,
+      })
+      .signers([staker.user])
+      .rpc();
+
+  const forceExit = (
+    pool: Pool,
+    staker: Position,
+    amount: number,
+    signer: Keypair = admin
+  ) =>
+    program.methods
+      .forceExit(new anchor.BN(amount), 1)
+      .accountsPartial({
+        stakeTokenMint: pool.stakeTokenMint,
+        admin: signer.publicKey,
+        stakeTokenVault: pool.stakeTokenVault,
+        staking: pool.staking,
+        rewardReserve: pool.rewardReserve,
+        user: staker.user.publicKey,
+        userStaking: staker.userStaking,
+        xTokenMint: pool.xTokenMint,
+        positionEscrow: staker.positionEscrow,
+        tokenTo: staker.stakeTokenAccount,
+        tokenProgram: pool.tokenProgram,
+        xTokenProgram: pool.xTokenProgram,
+      })
+      .signers([signer])
+      .rpc();
+
+  const emergencyWithdraw = (pool: Pool, staker: Position) =>
+    program.methods
+      .emergencyWithdraw()
+      .accountsPartial({
+        stakeTokenMint: pool.stakeTokenMint,
+        user: staker.user.publicKey,
+        stakeTokenVault: pool.stakeTokenVault,
+        staking: pool.staking,
+        rewardReserve: pool.rewardReserve,
+        userStaking: staker.userStaking,
+        xTokenMint: pool.xTokenMint,
+        positionEscrow: staker.positionEscrow,
+        tokenTo: staker.stakeTokenAccount,
+        tokenProgram: pool.tokenProgram,
+        xTokenProgram: pool.xTokenProgram,
+      })
+      .signers([staker.user])
+      .rpc();
+
+  const setEmergencyMode = (pool: Pool, enabled: boolean) =>
+    program.methods
+      .setEmergencyMode(enabled)
+      .accountsPartial({
+        initializer: admin.publicKey,
+        staking: pool.staking,
+      })
+      .rpc();
+
+  describe("initialize", () => {
+    it("sets up the pool for the upgrade authority", async () => {
+      const mint = await createMint(
+        provider.connection,
+        admin,
+        admin.publicKey,
+        null,
+        6
+      );
+      await initialize(mint, admin);
+
+      const { staking, xTokenMint } = poolAccounts(mint);
+      const pool = await program.account.stakingAccount.fetch(staking);
+      assert.ok(pool.initializerKey.equals(admin.publicKey));
+      assert.ok(pool.stakeTokenMint.equals(mint));
+      assert.ok(pool.xTokenMint.equals(xTokenMint));
+      assert.equal(pool.lockTiers[3].multiplierBps, 15000);
+    });
+
+    it("refuses to run twice", async () => {
+      const mint = await createMint(
+        provider.connection,
+        admin,
+        admin.publicKey,
+        null,
+        6
+      );
+      await initialize(mint, admin);
+
+      try {
+        await initialize(mint, admin);
+        assert.fail("initialized the pool twice");
+      } catch (err) {
+        assert.match((err.logs ?? []).join("\n"), /already in use/);
+      }
+    });
+
+    it("refuses a signer that is not the upgrade authority", async () => {
+      const mint = await createMint(
+        provider.connection,
+        admin,
+        admin.publicKey,
+        null,
+        6
+      );
+      const other = Keypair.generate();
+      await fund(other.publicKey);
+
+      try {
+        await initialize(mint, other);
+        assert.fail("initialized by a non upgrade authority");
+      } catch (err) {
+        assert.equal(
+          (err as anchor.AnchorError).error.errorCode.code,
+          "ConstraintRaw"
+        );
+      }
+
+      const pool = await program.account.stakingAccount.fetchNullable(
+        poolAccounts(mint).staking
+      );
+      assert.isNull(pool);
+    });
+  });
+
+  describe("release", () => {
+    it("keeps the shares in the escrow until the lock ends", async () => {
+      const pool = await createPool();
+      const staker = await createStaker(pool);
+      await stake(pool, staker, 3);
+
+      await expectError(release(pool, staker, 1), "NotExceedLockEndDate");
+      assert.equal(await balance(staker.positionEscrow), stakeAmount);
+      assert.equal(await balance(staker.xTokenAccount), 0);
+    });
+
+    it("moves unlocked shares into the wallet", async () => {
+      const pool = await createPool();
+      const staker = await createStaker(pool);
+      await stake(pool, staker, 0);
+
+      await release(pool, staker, stakeAmount);
+      assert.equal(await balance(staker.positionEscrow), 0);
+      assert.equal(await balance(staker.xTokenAccount), stakeAmount);
+
+      const position = await program.account.userStakingAccount.fetch(
+        staker.userStaking
+      );
+      assert.equal(position.rewardShares.toNumber(), 0);
+    });
+
+    it("is closed in emergency mode", async () => {
+      const pool = await createPool();
+      const staker = await createStaker(pool);
+      await stake(pool, staker, 0);
+      await setEmergencyMode(pool, true);
+
+      await expectError(release(pool, staker, stakeAmount), "EmergencyMode");
+    });
+  });
+
+  describe("force_exit", () => {
+    it("is admin only", async () => {
+      const pool = await createPool();
+      const staker = await createStaker(pool);
+      await stake(pool, staker, 0);
+
+      await expectError(
+        forceExit(pool, staker, stakeAmount, staker.user),
+        "Unauthorized"
+      );
+    });
+
+    it("respects the lock outside of emergency mode", async () => {
+      const pool = await createPool();
+      const staker = await createStaker(pool);
+      await stake(pool, staker, 3);
+
+      await expectError(
+        forceExit(pool, staker, stakeAmount),
+        "NotExceedLockEndDate"
+      );
+      assert.equal(await balance(staker.positionEscrow), stakeAmount);
+    });
+
+    it("pays out unlocked positions", async () => {
+      const pool = await createPool();
+      const staker = await createStaker(pool);
+      await stake(pool, staker, 0);
+
+      await forceExit(pool, staker, stakeAmount);
+      assert.equal(await balance(staker.positionEscrow), 0);
+      assert.equal(await balance(staker.stakeTokenAccount), stakeAmount);
+    });
+
+    it("bypasses the lock in emergency mode", async () => {
+      const pool = await createPool();
+      const staker = await createStaker(pool);
+      await stake(pool, staker, 3);
+      await setEmergencyMode(pool, true);
+
+      await forceExit(pool, staker, stakeAmount);
+      assert.equal(await balance(staker.positionEscrow), 0);
+      assert.equal(await balance(staker.stakeTokenAccount), stakeAmount);
+
+      const position = await program.account.userStakingAccount.fetch(
+        staker.userStaking
+      );
+      assert.equal(position.amount.toNumber(), 0);
+      assert.equal(position.rewardShares.toNumber(), 0);
+    });
+  });
+
+  describe("emergency_withdraw", () => {
+    it("is only open in emergency mode", async () => {
+      const pool = await createPool();
+      const staker = await createStaker(pool);
+      await stake(pool, staker, 3);
+
+      await expectError(emergencyWithdraw(pool, staker), "NotEmergencyMode");
+    });
+
+    it("pays the principal of a locked position and closes it", async () => {
+      const pool = await createPool();
+      const staker = await createStaker(pool);
+      await stake(pool, staker, 3);
+      await setEmergencyMode(pool, true);
+
+      await emergencyWithdraw(pool, staker);
+      assert.equal(await balance(staker.stakeTokenAccount), stakeAmount);
+      assert.isNull(
+        await program.account.userStakingAccount.fetchNullable(
+          staker.userStaking
+        )
+      );
+      assert.isNull(
+        await provider.connection.getAccountInfo(staker.positionEscrow)
+      );
 
-  it("Is initialized!", async () => {
-    // Add your test here.
-    const tx = await program.methods.initialize().rpc();
-    console.log("Your transaction signature", tx);
+      const staking = await program.account.stakingAccount.fetch(pool.staking);
+      assert.equal(staking.totalStaked.toNumber(), 0);
+      assert.equal(staking.totalRewardShares.toNumber(), 0);
+    });
   });
 });